@@ -13,7 +13,7 @@ use isa::*;
 
 /// Decode an instruction
 pub fn decode<M: Memory>(memory: &M, address: Address) -> Instruction {
-    use self::Flag::*;
+    use self::Condition::*;
     use self::Instruction::*;
     use self::Register16::*;
     use self::Register8::*;
@@ -48,13 +48,13 @@ pub fn decode<M: Memory>(memory: &M, address: Address) -> Instruction {
             0x16 => RlHl,
             0x17 => RlR(A),
             0x18 => RrR(B),
-            0x19 => RlR(C),
-            0x1A => RlR(D),
-            0x1B => RlR(E),
-            0x1C => RlR(H),
-            0x1D => RlR(L),
-            0x1E => RlHl,
-            0x1F => RlR(A),
+            0x19 => RrR(C),
+            0x1A => RrR(D),
+            0x1B => RrR(E),
+            0x1C => RrR(H),
+            0x1D => RrR(L),
+            0x1E => RrHl,
+            0x1F => RrR(A),
 
             0x20 => SlaR(B),
             0x21 => SlaR(C),
@@ -326,23 +326,23 @@ pub fn decode<M: Memory>(memory: &M, address: Address) -> Instruction {
         0x17 => Rla,
         0x18 => JrS(memory.read(next_address) as SignedImmediate8),
         0x19 => AddHlRr(DE),
-        0x1A => LdARr(BC),
+        0x1A => LdARr(DE),
         0x1B => DecRr(DE),
         0x1C => IncR(E),
         0x1D => DecR(E),
         0x1E => LdRI(E, memory.read(next_address) as Immediate8),
         0x1F => Rra,
 
-        0x20 => JrCondS(AddSub, memory.read(next_address) as SignedImmediate8),
+        0x20 => JrCondS(NotZero, memory.read(next_address) as SignedImmediate8),
         0x21 => LdRrIi(HL, memory.read_double(next_address) as Immediate16),
         0x22 => LdiHlA,
-        0x23 => IncHl,
+        0x23 => IncRr(HL),
         0x24 => IncR(H),
         0x25 => DecR(H),
         0x26 => LdRI(H, memory.read(next_address) as Immediate8),
         0x27 => Daa,
         0x28 => JrCondS(Zero, memory.read(next_address) as SignedImmediate8),
-        0x29 => AddHlRr(DE),
+        0x29 => AddHlRr(HL),
         0x2A => LdiAHl,
         0x2B => DecRr(HL),
         0x2C => IncR(L),
@@ -350,7 +350,7 @@ pub fn decode<M: Memory>(memory: &M, address: Address) -> Instruction {
         0x2E => LdRI(L, memory.read(next_address) as Immediate8),
         0x2F => Cpl,
 
-        0x30 => JrCondS(Carry, memory.read(next_address) as SignedImmediate8),
+        0x30 => JrCondS(NotCarry, memory.read(next_address) as SignedImmediate8),
         0x31 => LdRrIi(SP, memory.read_double(next_address) as Immediate16),
         0x32 => LddHlA,
         0x33 => IncRr(SP),
@@ -359,7 +359,7 @@ pub fn decode<M: Memory>(memory: &M, address: Address) -> Instruction {
         0x36 => LdHlI(memory.read(next_address) as Immediate8),
         0x37 => Scf,
         0x38 => JrCondS(Carry, memory.read(next_address) as SignedImmediate8),
-        0x39 => IncR(A),
+        0x39 => AddHlRr(SP),
         0x3A => LddAHl,
         0x3B => DecRr(SP),
         0x3C => IncR(A),
@@ -424,7 +424,7 @@ pub fn decode<M: Memory>(memory: &M, address: Address) -> Instruction {
         0x73 => LdHlR(E),
         0x74 => LdHlR(H),
         0x75 => LdHlR(L),
-        0x76 => Stop,
+        0x76 => Halt,
         0x77 => LdHlR(A),
         0x78 => LdRR(A, B),
         0x79 => LdRR(A, C),
@@ -503,14 +503,14 @@ pub fn decode<M: Memory>(memory: &M, address: Address) -> Instruction {
         0xBE => CpAHl,
         0xBF => CpAR(A),
 
-        0xC0 => RetCond(Carry),
+        0xC0 => RetCond(NotZero),
         0xC1 => Pop(BC),
-        0xC2 => JpCondIi(Zero, memory.read_double(next_address) as Address),
+        0xC2 => JpCondIi(NotZero, memory.read_double(next_address) as Address),
         0xC3 => JpIi(memory.read_double(next_address) as Address),
-        0xC4 => CallCondIi(Zero, memory.read_double(next_address) as Address),
+        0xC4 => CallCondIi(NotZero, memory.read_double(next_address) as Address),
         0xC5 => PushRr(BC),
         0xC6 => AddAI(memory.read(next_address) as Immediate8),
-        0xC7 => Rst(memory.read(next_address) as Immediate8),
+        0xC7 => Rst(0x00),
         0xC8 => RetCond(Zero),
         0xC9 => Ret,
         0xCA => JpCondIi(Zero, memory.read_double(next_address) as Address),
@@ -518,16 +518,16 @@ pub fn decode<M: Memory>(memory: &M, address: Address) -> Instruction {
         0xCC => CallCondIi(Zero, memory.read_double(next_address) as Address),
         0xCD => CallIi(memory.read_double(next_address) as Address),
         0xCE => AdcAI(memory.read(next_address) as Immediate8),
-        0xCF => Rst(memory.read(next_address) as Immediate8),
+        0xCF => Rst(0x08),
 
-        0xD0 => RetCond(Carry),
+        0xD0 => RetCond(NotCarry),
         0xD1 => Pop(DE),
-        0xD2 => JpCondIi(Zero, memory.read_double(next_address) as Address),
+        0xD2 => JpCondIi(NotCarry, memory.read_double(next_address) as Address),
         v @ 0xD3 => Undefined(v),
-        0xD4 => CallCondIi(Zero, memory.read_double(next_address) as Address),
+        0xD4 => CallCondIi(NotCarry, memory.read_double(next_address) as Address),
         0xD5 => PushRr(DE),
         0xD6 => SubAI(memory.read(next_address) as Immediate8),
-        0xD7 => Rst(memory.read(next_address) as Immediate8),
+        0xD7 => Rst(0x10),
         0xD8 => RetCond(Carry),
         0xD9 => Reti,
         0xDA => JpCondIi(Carry, memory.read_double(next_address) as Address),
@@ -535,7 +535,7 @@ pub fn decode<M: Memory>(memory: &M, address: Address) -> Instruction {
         0xDC => CallCondIi(Carry, memory.read_double(next_address) as Address),
         v @ 0xDD => Undefined(v),
         0xDE => SbcAI(memory.read(next_address) as Immediate8),
-        0xDF => Rst(memory.read(next_address) as Immediate8),
+        0xDF => Rst(0x18),
 
         0xE0 => LdIoA(memory.read(next_address) as Immediate8),
         0xE1 => Pop(HL),
@@ -544,15 +544,15 @@ pub fn decode<M: Memory>(memory: &M, address: Address) -> Instruction {
         v @ 0xE4 => Undefined(v),
         0xE5 => PushRr(HL),
         0xE6 => AndAI(memory.read(next_address) as Immediate8),
-        0xE7 => Rst(memory.read(next_address) as Immediate8),
+        0xE7 => Rst(0x20),
         0xE8 => AddSpS(memory.read(next_address) as SignedImmediate8),
         0xE9 => JpHl,
-        0xEA => LdIiA,
+        0xEA => LdIiA(memory.read_double(next_address) as Address),
         v @ 0xEB => Undefined(v),
         v @ 0xEC => Undefined(v),
         v @ 0xED => Undefined(v),
         0xEE => XorAI(memory.read(next_address) as Immediate8),
-        0xEF => Rst(memory.read(next_address) as Immediate8),
+        0xEF => Rst(0x28),
 
         0xF0 => LdAIo(memory.read(next_address) as Immediate8),
         0xF1 => Pop(AF),
@@ -561,15 +561,15 @@ pub fn decode<M: Memory>(memory: &M, address: Address) -> Instruction {
         v @ 0xF4 => Undefined(v),
         0xF5 => PushRr(AF),
         0xF6 => OrAI(memory.read(next_address) as Immediate8),
-        0xF7 => Rst(memory.read(next_address) as Immediate8),
+        0xF7 => Rst(0x30),
         0xF8 => LdHlSpS(memory.read(next_address) as SignedImmediate8),
         0xF9 => LdSpHl,
-        0xFA => unimplemented!(), // TODO: implement me
+        0xFA => LdAIi(memory.read_double(next_address) as Address),
         0xFB => Ei,
         v @ 0xFC => Undefined(v),
         v @ 0xFD => Undefined(v),
         0xFE => CpAI(memory.read(next_address) as Immediate8),
-        0xFF => Rst(memory.read(next_address) as Immediate8),
+        0xFF => Rst(0x38),
         _ => unreachable!(),
     }
 }
@@ -6,6 +6,7 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use hardware::bios::Bios;
 use hardware::mmu::Swram;
 use hardware::Mmu;
 
@@ -17,7 +18,7 @@ pub struct Apu {
 
 impl Apu {
     /// Emulate the function of an `APU` over a specified number of cycles
-    pub fn emulate<S: Swram>(&mut self, cycles: usize, mmu: &mut Mmu<S>) {
+    pub fn emulate<S: Swram, B: Bios>(&mut self, cycles: usize, mmu: &mut Mmu<S, B>) {
         debug!("APU emulation not yet implemented")
     }
 }
@@ -15,12 +15,18 @@ use std::fmt::{self, Debug};
 const GB_BIOS_SIZE: usize = 0x100;
 const CGB_BIOS_SIZE: usize = 0x900;
 
+/// First address after the cartridge header gap in the CGB bios
+const CGB_BIOS_GAP_END: Address = 0x200;
+
 type GbBiosInner = [Word; GB_BIOS_SIZE];
 
 type CgbBiosInner = [Word; CGB_BIOS_SIZE];
 
-/// A Gameboy bios marker
-pub trait Bios: Memory {}
+/// A Gameboy bios
+pub trait Bios: Memory {
+    /// Returns true if the bios is overlaid on the cartridge at an address while it is mapped
+    fn maps(&self, address: Address) -> bool;
+}
 
 /// A Gameboy bios
 #[derive(Clone, Copy)]
@@ -37,7 +43,11 @@ impl Memory for GbBios {
     }
 }
 
-impl Bios for GbBios {}
+impl Bios for GbBios {
+    fn maps(&self, address: Address) -> bool {
+        address < GB_BIOS_SIZE as Address
+    }
+}
 
 impl From<GbBiosInner> for GbBios {
     fn from(bytes: GbBiosInner) -> Self {
@@ -67,7 +77,13 @@ impl Memory for CgbBios {
     }
 }
 
-impl Bios for CgbBios {}
+impl Bios for CgbBios {
+    fn maps(&self, address: Address) -> bool {
+        // the cartridge header stays visible between the two halves of the bios
+        address < GB_BIOS_SIZE as Address
+            || (address >= CGB_BIOS_GAP_END && address < CGB_BIOS_SIZE as Address)
+    }
+}
 
 impl From<CgbBiosInner> for CgbBios {
     fn from(bytes: CgbBiosInner) -> Self {
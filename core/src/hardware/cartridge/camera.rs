@@ -0,0 +1,284 @@
+// Copyright 2018 Will Johnston
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! M64282FP image sensor on the Game Boy Camera
+//!
+//! A capture exposes a frame from an image provider, enhances its edges, then dithers it down
+//! to 2-bit colour through a 4x4 matrix of thresholds. The result is written to the start of
+//! ram bank 0 as tile data.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use isa::Word;
+
+/// Width of a captured frame in pixels
+pub const WIDTH: usize = 128;
+
+/// Height of a captured frame in pixels
+pub const HEIGHT: usize = 112;
+
+/// Number of sensor registers. The rest of the register bank mirrors them
+pub const REGISTER_COUNT: usize = 0x36;
+
+/// Capture start and busy bit of register 0
+const CAPTURE_BIT: Word = 0x01;
+
+/// Register 1 bit that disables the longer exposure reset
+const N_BIT: Word = 0x80;
+
+/// Register 1 bits that select vertical and horizontal edge enhancement
+const VH_BITS: Word = 0x60;
+
+/// Register 4 bit that inverts the output
+const INVERT_BIT: Word = 0x08;
+
+/// Offset of the dithering matrix in the registers. Each of the 16 entries holds 3 thresholds
+const MATRIX_OFFSET: usize = 0x06;
+
+/// Offset of the captured image in ram bank 0
+const IMAGE_OFFSET: usize = 0x100;
+
+/// Exposure that passes the sensor's light through unchanged
+const UNIT_EXPOSURE: u32 = 0x1000;
+
+/// Edge enhancement ratios, selected by bits 4 to 6 of register 4
+const EDGE_RATIOS: [f32; 8] = [0.5, 0.75, 1.0, 1.25, 2.0, 3.0, 4.0, 5.0];
+
+/// A shared image provider the camera captures frames from
+pub type ImagePort = Rc<RefCell<dyn ImageProvider>>;
+
+/// Something that can be photographed
+pub trait ImageProvider {
+    /// Fill a `WIDTH` by `HEIGHT` frame, row by row, with the brightness of each pixel from 0
+    /// for black to 0xFF for white
+    fn capture(&mut self, frame: &mut [Word]);
+}
+
+/// A fixed image, like a decoded picture
+#[derive(Debug, Clone)]
+pub struct StaticImage {
+    pixels: Vec<Word>,
+}
+
+impl StaticImage {
+    /// Create an image from brightness values, row by row. Missing pixels are black
+    pub fn new(pixels: &[Word]) -> Self {
+        let mut image = vec![0; WIDTH * HEIGHT];
+        for (pixel, &value) in image.iter_mut().zip(pixels) {
+            *pixel = value;
+        }
+        StaticImage { pixels: image }
+    }
+}
+
+impl ImageProvider for StaticImage {
+    fn capture(&mut self, frame: &mut [Word]) {
+        frame.copy_from_slice(&self.pixels);
+    }
+}
+
+/// A generated test pattern of vertical bars, getting brighter from left to right
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TestPattern;
+
+impl ImageProvider for TestPattern {
+    fn capture(&mut self, frame: &mut [Word]) {
+        for (i, pixel) in frame.iter_mut().enumerate() {
+            *pixel = ((i % WIDTH) / (WIDTH / 4) * 0x55) as Word;
+        }
+    }
+}
+
+/// The camera sensor and its registers
+#[derive(Clone)]
+pub struct Camera {
+    registers: [Word; REGISTER_COUNT],
+    /// Cycles left until the capture in progress finishes
+    capture_cycles: usize,
+    image: Option<ImagePort>,
+}
+
+impl Camera {
+    /// Connect the image provider frames are captured from
+    pub fn connect(&mut self, port: ImagePort) {
+        self.image = Some(port)
+    }
+
+    /// Returns true while a capture is in progress
+    pub fn is_capturing(&self) -> bool {
+        self.capture_cycles > 0
+    }
+
+    /// Read a register. Only register 0 is readable, the rest read as 0
+    pub fn read(&self, register: usize) -> Word {
+        match register % 0x80 {
+            0 => (self.registers[0] & !CAPTURE_BIT) | self.is_capturing() as Word,
+            _ => 0x00,
+        }
+    }
+
+    /// Write a register. Setting the capture bit of register 0 starts a capture
+    pub fn write(&mut self, register: usize, value: Word) {
+        match register % 0x80 {
+            0 => {
+                self.registers[0] = value & 0x07;
+                if value & CAPTURE_BIT != 0 && !self.is_capturing() {
+                    self.capture_cycles = self.capture_duration();
+                }
+            }
+            register if register < REGISTER_COUNT => self.registers[register] = value,
+            _ => {}
+        }
+    }
+
+    /// Emulate the sensor over a given number of cycles, writing the image to `ram` once a
+    /// capture finishes
+    pub fn emulate(&mut self, cycles: usize, ram: &mut [Word]) {
+        if !self.is_capturing() {
+            return;
+        }
+
+        self.capture_cycles = self.capture_cycles.saturating_sub(cycles);
+        if !self.is_capturing() {
+            self.capture(ram);
+        }
+    }
+
+    fn exposure(&self) -> u32 {
+        u32::from(self.registers[2]) << 8 | u32::from(self.registers[3])
+    }
+
+    /// Return the number of cycles a capture takes with the current registers
+    fn capture_duration(&self) -> usize {
+        let reset = if self.registers[1] & N_BIT != 0 {
+            0
+        } else {
+            512
+        };
+        4 * (32446 + reset + 16 * self.exposure() as usize)
+    }
+
+    /// Capture a frame and write it to the start of ram as tiles
+    fn capture(&mut self, ram: &mut [Word]) {
+        let mut frame = vec![0; WIDTH * HEIGHT];
+        if let Some(ref image) = self.image {
+            image.borrow_mut().capture(&mut frame);
+        }
+
+        let exposure = self.exposure();
+        let exposed: Vec<f32> = frame
+            .iter()
+            .map(|&pixel| (u32::from(pixel) * exposure / UNIT_EXPOSURE).min(0xFF) as f32)
+            .collect();
+
+        let enhance = self.registers[1] & VH_BITS == VH_BITS;
+        let ratio = EDGE_RATIOS[usize::from(self.registers[4] >> 4) & 0x07];
+        let invert = self.registers[4] & INVERT_BIT != 0;
+
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let pixel = |x: usize, y: usize| exposed[y * WIDTH + x];
+                let mut value = pixel(x, y);
+                if enhance {
+                    let neighbours = pixel(x.saturating_sub(1), y)
+                        + pixel((x + 1).min(WIDTH - 1), y)
+                        + pixel(x, y.saturating_sub(1))
+                        + pixel(x, (y + 1).min(HEIGHT - 1));
+                    value = value * (1.0 + ratio) - neighbours * ratio / 4.0;
+                }
+
+                let mut value = value.clamp(0.0, 255.0) as Word;
+                if invert {
+                    value = !value;
+                }
+
+                self.write_pixel(ram, x, y, self.dither(x, y, value));
+            }
+        }
+    }
+
+    /// Reduce a pixel to a 2-bit colour, where 0 is white, using the thresholds for its
+    /// position in the dithering matrix
+    fn dither(&self, x: usize, y: usize, value: Word) -> Word {
+        let entry = MATRIX_OFFSET + ((y % 4) * 4 + x % 4) * 3;
+        let thresholds = &self.registers[entry..entry + 3];
+        thresholds
+            .iter()
+            .position(|&threshold| value < threshold)
+            .map_or(0, |darker| 3 - darker as Word)
+    }
+
+    fn write_pixel(&self, ram: &mut [Word], x: usize, y: usize, color: Word) {
+        let tile = (y / 8) * (WIDTH / 8) + x / 8;
+        let index = IMAGE_OFFSET + tile * 16 + (y % 8) * 2;
+        if index + 1 >= ram.len() {
+            return;
+        }
+
+        let bit = 0x80 >> (x % 8);
+        for (plane, byte) in ram[index..index + 2].iter_mut().enumerate() {
+            if color >> plane & 0x01 != 0 {
+                *byte |= bit;
+            } else {
+                *byte &= !bit;
+            }
+        }
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera {
+            registers: [0; REGISTER_COUNT],
+            capture_cycles: 0,
+            image: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn captures_dithered_test_pattern() {
+        let mut camera = Camera::default();
+        camera.connect(Rc::new(RefCell::new(TestPattern)));
+        camera.write(0x02, 0x10); // unit exposure
+        for entry in 0..16 {
+            camera.write(MATRIX_OFFSET + entry * 3, 0x40);
+            camera.write(MATRIX_OFFSET + entry * 3 + 1, 0x80);
+            camera.write(MATRIX_OFFSET + entry * 3 + 2, 0xC0);
+        }
+
+        let mut ram = vec![0; 0x2000];
+        camera.write(0x00, CAPTURE_BIT);
+        assert_eq!(camera.read(0x00), CAPTURE_BIT);
+        camera.emulate(camera.capture_duration() - 1, &mut ram);
+        assert!(camera.is_capturing());
+        camera.emulate(1, &mut ram);
+        assert_eq!(camera.read(0x00), 0x00);
+
+        // the first tile column is black, and each quarter of the frame gets lighter
+        let row = |tile: usize| (ram[IMAGE_OFFSET + tile * 16], ram[IMAGE_OFFSET + tile * 16 + 1]);
+        assert_eq!(row(0), (0xFF, 0xFF));
+        assert_eq!(row(4), (0x00, 0xFF));
+        assert_eq!(row(8), (0xFF, 0x00));
+        assert_eq!(row(12), (0x00, 0x00));
+    }
+
+    #[test]
+    fn only_register_zero_reads_back() {
+        let mut camera = Camera::default();
+        camera.write(0x04, 0x08);
+        camera.write(0x00, 0x06);
+        assert_eq!(camera.read(0x04), 0x00);
+        assert_eq!(camera.read(0x80), 0x06);
+    }
+}
@@ -0,0 +1,273 @@
+// Copyright 2018 Will Johnston
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! 93LC56 serial EEPROM, as wired to the MBC7
+//!
+//! Commands are clocked in one bit at a time while chip select is high. Each starts with a
+//! set start bit, followed by a 2-bit opcode and an 8-bit address or sub-opcode.
+
+use isa::Word;
+
+/// Number of 16-bit words held by the EEPROM
+const WORDS: usize = 128;
+
+/// Number of bits in a command, including the start bit
+const COMMAND_BITS: u8 = 11;
+
+const OPCODE_EXTENDED: u16 = 0b00;
+const OPCODE_WRITE: u16 = 0b01;
+const OPCODE_READ: u16 = 0b10;
+const OPCODE_ERASE: u16 = 0b11;
+
+const EXTENDED_DISABLE_WRITES: u16 = 0b00;
+const EXTENDED_WRITE_ALL: u16 = 0b01;
+const EXTENDED_ERASE_ALL: u16 = 0b10;
+const EXTENDED_ENABLE_WRITES: u16 = 0b11;
+
+/// What the EEPROM does on the next clock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Shifting in a command
+    Command { bits: u8, command: u16 },
+    /// Shifting out a word, most significant bit first, followed by the next word
+    Read { address: u8, bits: u8 },
+    /// Shifting in a word to write to one address, or every address
+    Write {
+        address: Option<u8>,
+        bits: u8,
+        value: u16,
+    },
+}
+
+const IDLE: State = State::Command {
+    bits: 0,
+    command: 0,
+};
+
+/// A 93LC56 serial EEPROM in 16-bit mode
+#[derive(Debug, Clone)]
+pub struct Eeprom {
+    /// Words stored low byte first
+    data: Vec<Word>,
+    write_enabled: bool,
+    state: State,
+    chip_select: bool,
+    clock: bool,
+    data_in: bool,
+    data_out: bool,
+    /// Whether a store changed the contents since the pins were last driven
+    changed: bool,
+}
+
+impl Eeprom {
+    /// Return the contents of the EEPROM, with each word stored low byte first
+    pub fn data(&self) -> &[Word] {
+        &self.data
+    }
+
+    /// Return the contents of the EEPROM mutably, with each word stored low byte first
+    pub fn data_mut(&mut self) -> &mut [Word] {
+        &mut self.data
+    }
+
+    /// Return the pins as seen through the MBC7: chip select in bit 7, clock in bit 6, data in
+    /// in bit 1 and data out in bit 0
+    pub fn read_pins(&self) -> Word {
+        (self.chip_select as Word) << 7
+            | (self.clock as Word) << 6
+            | (self.data_in as Word) << 1
+            | self.data_out as Word
+    }
+
+    /// Drive the pins, laid out as in `read_pins`. Returns true if this completed a command
+    /// that changed the contents
+    pub fn write_pins(&mut self, value: Word) -> bool {
+        self.changed = false;
+        let chip_select = value & 0x80 != 0;
+        let clock = value & 0x40 != 0;
+        self.data_in = value & 0x02 != 0;
+
+        if !chip_select {
+            // deselecting aborts any command and reports ready
+            self.state = IDLE;
+            self.data_out = true;
+        } else if clock && !self.clock {
+            self.rising_edge();
+        }
+
+        self.chip_select = chip_select;
+        self.clock = clock;
+        self.changed
+    }
+
+    fn word(&self, address: u8) -> u16 {
+        let i = usize::from(address) * 2;
+        u16::from(self.data[i]) | u16::from(self.data[i + 1]) << 8
+    }
+
+    fn set_word(&mut self, address: u8, value: u16) {
+        if self.write_enabled && self.word(address) != value {
+            let i = usize::from(address) * 2;
+            self.data[i] = value as Word;
+            self.data[i + 1] = (value >> 8) as Word;
+            self.changed = true;
+        }
+    }
+
+    fn rising_edge(&mut self) {
+        let bit = u16::from(self.data_in);
+        self.state = match self.state {
+            // leading zeros before the start bit are ignored
+            State::Command { bits: 0, .. } if bit == 0 => IDLE,
+            State::Command { bits, command } if bits + 1 < COMMAND_BITS => State::Command {
+                bits: bits + 1,
+                command: command << 1 | bit,
+            },
+            State::Command { command, .. } => self.run((command << 1 | bit) & 0x3FF),
+            State::Read { address, bits } => {
+                let value = self.word(address);
+                self.data_out = value & (0x8000 >> bits) != 0;
+                if bits == 15 {
+                    State::Read {
+                        address: (address + 1) % WORDS as u8,
+                        bits: 0,
+                    }
+                } else {
+                    State::Read {
+                        address,
+                        bits: bits + 1,
+                    }
+                }
+            }
+            State::Write {
+                address,
+                bits,
+                value,
+            } => {
+                let value = value << 1 | bit;
+                if bits < 15 {
+                    State::Write {
+                        address,
+                        bits: bits + 1,
+                        value,
+                    }
+                } else {
+                    match address {
+                        Some(address) => self.set_word(address, value),
+                        None => (0..WORDS as u8).for_each(|address| self.set_word(address, value)),
+                    }
+                    self.data_out = true;
+                    IDLE
+                }
+            }
+        };
+    }
+
+    /// Run a received command, without its start bit
+    fn run(&mut self, command: u16) -> State {
+        let address = (command & 0x7F) as u8;
+        match command >> 8 {
+            OPCODE_READ => {
+                // a dummy zero bit comes out before the data
+                self.data_out = false;
+                State::Read { address, bits: 0 }
+            }
+            OPCODE_WRITE => State::Write {
+                address: Some(address),
+                bits: 0,
+                value: 0,
+            },
+            OPCODE_ERASE => {
+                self.set_word(address, 0xFFFF);
+                self.data_out = true;
+                IDLE
+            }
+            OPCODE_EXTENDED => match (command >> 6) & 0x03 {
+                EXTENDED_DISABLE_WRITES => {
+                    self.write_enabled = false;
+                    IDLE
+                }
+                EXTENDED_WRITE_ALL => State::Write {
+                    address: None,
+                    bits: 0,
+                    value: 0,
+                },
+                EXTENDED_ERASE_ALL => {
+                    (0..WORDS as u8).for_each(|address| self.set_word(address, 0xFFFF));
+                    self.data_out = true;
+                    IDLE
+                }
+                EXTENDED_ENABLE_WRITES => {
+                    self.write_enabled = true;
+                    IDLE
+                }
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Default for Eeprom {
+    fn default() -> Self {
+        Eeprom {
+            data: vec![0xFF; WORDS * 2],
+            write_enabled: false,
+            state: IDLE,
+            chip_select: false,
+            clock: false,
+            data_in: false,
+            data_out: true,
+            changed: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Clock bits into the EEPROM, returning what it put on data out after each
+    fn clock_bits(eeprom: &mut Eeprom, bits: &[u8]) -> Vec<u8> {
+        bits.iter()
+            .map(|&bit| {
+                eeprom.write_pins(0x80 | bit << 1);
+                eeprom.write_pins(0xC0 | bit << 1);
+                eeprom.read_pins() & 0x01
+            })
+            .collect()
+    }
+
+    fn command(start_opcode: u16, operand: u16) -> Vec<u8> {
+        let command = start_opcode << 8 | operand;
+        (0..COMMAND_BITS).rev().map(|i| (command >> i) as u8 & 1).collect()
+    }
+
+    #[test]
+    fn writes_then_reads_a_word() {
+        let mut eeprom = Eeprom::default();
+        let data: Vec<u8> = (0..16).rev().map(|i| (0xBEEF >> i) as u8 & 1).collect();
+
+        // writes are ignored until enabled
+        clock_bits(&mut eeprom, &command(0b101, 0x05));
+        clock_bits(&mut eeprom, &data);
+        eeprom.write_pins(0x00);
+        assert_eq!(eeprom.word(0x05), 0xFFFF);
+
+        clock_bits(&mut eeprom, &command(0b100, 0xC0));
+        eeprom.write_pins(0x00);
+        clock_bits(&mut eeprom, &command(0b101, 0x05));
+        clock_bits(&mut eeprom, &data);
+        eeprom.write_pins(0x00);
+        assert_eq!(eeprom.word(0x05), 0xBEEF);
+
+        let read = clock_bits(&mut eeprom, &command(0b110, 0x05));
+        assert_eq!(read.last(), Some(&0));
+        assert_eq!(clock_bits(&mut eeprom, &[0; 16]), data);
+    }
+}
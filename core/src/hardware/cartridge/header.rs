@@ -8,7 +8,12 @@
 
 //! Gameboy cartridge header types
 
-use isa::{Address, Word};
+use std::convert::TryFrom;
+
+use failure::Error;
+use hardware::cartridge::licensee::{Licensee, USE_NEW_LICENSEE_CODE};
+use hardware::cartridge::ParsingError;
+use isa::{Address, DoubleWord, Word};
 
 /// Start of game entry point multi-byte header field
 pub const ENTRY_POINT_OFFSET: Address = 0x100;
@@ -22,6 +27,13 @@ pub const NINTENDO_LOGO_OFFSET: Address = 0x104;
 /// End of nintendo logo bitmap multi-byte header field
 pub const NINTENDO_LOGO_END: Address = 0x133;
 
+/// The nintendo logo bitmap every licensed cartridge carries in its header
+pub const NINTENDO_LOGO: [Word; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+
 /// Start of title multi-byte header field
 pub const TITLE_OFFSET: Address = 0x134;
 /// End of title multi-byte header field
@@ -66,8 +78,186 @@ pub const GLOBAL_CHECKSUM_OFFSET: Address = 0x14E;
 /// End of checksum to verify the contents of the cartridge multi-byte field
 pub const GLOBAL_CHECKSUM_END: Address = 0x14F;
 
+/// Start of the manufacturer code in the title of newer color gameboy cartridges
+pub const MANUFACTURER_CODE_OFFSET: Address = 0x13F;
+
+/// Color gameboy flag bit set by cartridges that support the color gameboy
+const CGB_SUPPORT_BIT: Word = 0x80;
+
+/// Color gameboy flag bit set by cartridges that only run on the color gameboy
+const CGB_ONLY_BIT: Word = 0x40;
+
+/// Super gameboy flag value of cartridges that support super gameboy functions
+const SGB_SUPPORT_VALUE: Word = 0x03;
+
+/// The decoded header of a cartridge
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CartridgeHeader {
+    /// Title in upper case ascii, without padding
+    pub title: String,
+    /// Four character manufacturer code, only found on newer color gameboy cartridges
+    pub manufacturer_code: Option<String>,
+    pub cgb_support: CgbSupport,
+    pub sgb_support: bool,
+    pub licensee: Licensee,
+    pub kind: CartridgeKind,
+    pub rom_size: RomSize,
+    pub ram_size: RamSize,
+    pub destination: Destination,
+    /// Version number of the game, usually 0
+    pub version: Word,
+    /// Checksum of the header bytes from the title to the version
+    pub header_checksum: Word,
+    /// Sum of every byte of the rom, except the checksum itself
+    pub global_checksum: DoubleWord,
+}
+
+impl CartridgeHeader {
+    /// Try to decode the header at the start of a rom, returning an error if any occurred
+    pub fn try_parse_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() <= GLOBAL_CHECKSUM_END as usize {
+            return Err(ParsingError::InvalidHeaderLength {
+                length: bytes.len(),
+            }.into());
+        }
+
+        let byte = |address: Address| bytes[address as usize];
+        let cgb_support = CgbSupport::from(byte(CGB_FLAG_ADDRESS));
+
+        let code = &bytes[MANUFACTURER_CODE_OFFSET as usize..CGB_FLAG_ADDRESS as usize];
+        let has_manufacturer_code = cgb_support != CgbSupport::None
+            && code.iter().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit());
+        let (title_end, manufacturer_code) = match cgb_support {
+            CgbSupport::None => (TITLE_END + 1, None),
+            _ if has_manufacturer_code => (
+                MANUFACTURER_CODE_OFFSET,
+                Some(String::from_utf8_lossy(code).into_owned()),
+            ),
+            _ => (CGB_FLAG_ADDRESS, None),
+        };
+        let title = bytes[TITLE_OFFSET as usize..title_end as usize]
+            .iter()
+            .take_while(|&&c| c != 0)
+            .map(|&c| c as char)
+            .collect::<String>()
+            .trim_end()
+            .to_owned();
+
+        let licensee = match byte(OLD_LICENSEE_CODE_ADDRESS) {
+            USE_NEW_LICENSEE_CODE => Licensee::New([
+                byte(NEW_LICENSEE_CODE_OFFSET),
+                byte(NEW_LICENSEE_CODE_END),
+            ]),
+            code => Licensee::Old(code),
+        };
+
+        Ok(CartridgeHeader {
+            title,
+            manufacturer_code,
+            cgb_support,
+            sgb_support: byte(SGB_FLAG_ADDRESS) == SGB_SUPPORT_VALUE,
+            licensee,
+            kind: CartridgeKind::try_from(byte(CATRIDGE_TYPE_ADDRESS))?,
+            rom_size: RomSize::try_from(byte(ROM_SIZE_ADDRESS))?,
+            ram_size: RamSize::try_from(byte(RAM_SIZE_ADDRESS))?,
+            destination: Destination::try_from(byte(DESTINATION_CODE_ADDRESS))?,
+            version: byte(MASK_ROM_VERSION_ADDRESS),
+            header_checksum: byte(HEADER_CHECKSUM_ADDRESS),
+            global_checksum: DoubleWord::from(byte(GLOBAL_CHECKSUM_OFFSET)) << 8
+                | DoubleWord::from(byte(GLOBAL_CHECKSUM_END)),
+        })
+    }
+}
+
+/// Results of checking a rom against the checksums and logo in its header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Verification {
+    /// The header checksum matches. The boot rom refuses to start the cartridge otherwise
+    pub header_checksum: bool,
+    /// The global checksum matches. Nothing checks it on real hardware
+    pub global_checksum: bool,
+    /// The logo matches the reference bitmap. The boot rom refuses to start the cartridge
+    /// otherwise
+    pub logo: bool,
+}
+
+impl Verification {
+    /// Check a rom, returning an error if it's too short to hold a header
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let header = CartridgeHeader::try_parse_bytes(bytes)?;
+        let logo = NINTENDO_LOGO_OFFSET as usize..NINTENDO_LOGO_END as usize + 1;
+
+        Ok(Verification {
+            header_checksum: header_checksum(bytes) == header.header_checksum,
+            global_checksum: global_checksum(bytes) == header.global_checksum,
+            logo: bytes[logo] == NINTENDO_LOGO[..],
+        })
+    }
+
+    /// Returns true if every check passed
+    pub fn is_valid(&self) -> bool {
+        self.header_checksum && self.global_checksum && self.logo
+    }
+}
+
+/// Compute the header checksum of a rom, over the bytes from the title to the version
+pub fn header_checksum(bytes: &[u8]) -> Word {
+    bytes[TITLE_OFFSET as usize..MASK_ROM_VERSION_ADDRESS as usize + 1]
+        .iter()
+        .fold(0, |sum: Word, &byte| sum.wrapping_sub(byte).wrapping_sub(1))
+}
+
+/// Compute the global checksum of a rom, the sum of every byte except the checksum itself
+pub fn global_checksum(bytes: &[u8]) -> DoubleWord {
+    bytes
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| i != GLOBAL_CHECKSUM_OFFSET as usize && i != GLOBAL_CHECKSUM_END as usize)
+        .fold(0, |sum: DoubleWord, (_, &byte)| sum.wrapping_add(DoubleWord::from(byte)))
+}
+
+/// Rewrite the header and global checksums of a rom to match its contents, like `rgbfix -f hg`.
+/// Returns an error if the rom is too short to hold a header
+pub fn fix_checksums(bytes: &mut [u8]) -> Result<(), Error> {
+    if bytes.len() <= GLOBAL_CHECKSUM_END as usize {
+        return Err(ParsingError::InvalidHeaderLength {
+            length: bytes.len(),
+        }.into());
+    }
+
+    // the header checksum is part of the global checksum, so it's fixed first
+    bytes[HEADER_CHECKSUM_ADDRESS as usize] = header_checksum(bytes);
+    let global = global_checksum(bytes);
+    bytes[GLOBAL_CHECKSUM_OFFSET as usize] = (global >> 8) as Word;
+    bytes[GLOBAL_CHECKSUM_END as usize] = global as Word;
+    Ok(())
+}
+
+/// How a cartridge supports the color gameboy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CgbSupport {
+    /// Made for the original gameboy. The color gameboy runs it with a fixed palette
+    None,
+    /// Runs on every model, with color on the color gameboy
+    Compatible,
+    /// Only runs on the color gameboy
+    Only,
+}
+
+impl From<Word> for CgbSupport {
+    fn from(value: Word) -> Self {
+        if value & CGB_SUPPORT_BIT == 0 {
+            CgbSupport::None
+        } else if value & CGB_ONLY_BIT != 0 {
+            CgbSupport::Only
+        } else {
+            CgbSupport::Compatible
+        }
+    }
+}
+
 /// A cartridge kind
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CartridgeKind {
     RomOnly,
     MBC1,
@@ -99,11 +289,12 @@ pub enum CartridgeKind {
     HuC1RamBattery,
 }
 
-// TODO: change this to try_from once the `TryFrom` trait is stabilized
-impl From<Word> for CartridgeKind {
-    fn from(value: Word) -> Self {
+impl TryFrom<Word> for CartridgeKind {
+    type Error = ParsingError;
+
+    fn try_from(value: Word) -> Result<Self, ParsingError> {
         use self::CartridgeKind::*;
-        match value {
+        let kind = match value {
             0x00 => RomOnly,
             0x01 => MBC1,
             0x02 => MBC1Ram,
@@ -126,13 +317,15 @@ impl From<Word> for CartridgeKind {
             0x1C => MBC5Rumble,
             0x1D => MBC5RumbleRam,
             0x1E => MBC5RumbleRamBattery,
-            0x20 => MBC7SensorRumbleRamBattery,
+            0x20 => MBC6,
+            0x22 => MBC7SensorRumbleRamBattery,
             0xFC => PocketCamera,
             0xFD => BandaiTama5,
             0xFE => HuC3,
             0xFF => HuC1RamBattery,
-            _ => unreachable!(),
-        }
+            _ => return Err(ParsingError::InvalidCartridgeKind { value }),
+        };
+        Ok(kind)
     }
 }
 
@@ -140,31 +333,41 @@ impl CartridgeKind {
     /// Returns true if the cartridge kind has a battery
     pub fn has_battery(self) -> bool {
         use self::CartridgeKind::*;
-        match self {
+        matches!(
+            self,
             MBC1RamBattery
-            | MBC2Battery
-            | MBC3TimerBattery
-            | MBC3TimerRamBattery
-            | MBC5RamBattery
-            | MBC5RumbleRamBattery
-            | MBC7SensorRumbleRamBattery
-            | HuC1RamBattery => true,
-            _ => false,
-        }
+                | MBC2Battery
+                | MMM01RamBattery
+                | MBC3TimerBattery
+                | MBC3TimerRamBattery
+                | MBC5RamBattery
+                | MBC5RumbleRamBattery
+                | MBC7SensorRumbleRamBattery
+                | PocketCamera
+                | BandaiTama5
+                | HuC3
+                | HuC1RamBattery
+        )
+    }
+
+    /// Returns true if the cartridge kind has a rumble motor
+    pub fn has_rumble(self) -> bool {
+        use self::CartridgeKind::*;
+        matches!(
+            self,
+            MBC5Rumble | MBC5RumbleRam | MBC5RumbleRamBattery | MBC7SensorRumbleRamBattery
+        )
     }
 
     /// Returns true if the cartridge kind has a timer
     pub fn has_timer(self) -> bool {
         use self::CartridgeKind::*;
-        match self {
-            MBC3TimerBattery | MBC3TimerRamBattery => true,
-            _ => false,
-        }
+        matches!(self, MBC3TimerBattery | MBC3TimerRamBattery)
     }
 }
 
 /// A cartridge rom size flag value
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RomSize {
     Kb32,
     Kb64,
@@ -180,11 +383,12 @@ pub enum RomSize {
     Mb1_5,
 }
 
-// TODO: change this to try_from once the `TryFrom` trait is stabilized
-impl From<Word> for RomSize {
-    fn from(value: Word) -> Self {
+impl TryFrom<Word> for RomSize {
+    type Error = ParsingError;
+
+    fn try_from(value: Word) -> Result<Self, ParsingError> {
         use self::RomSize::*;
-        match value {
+        let size = match value {
             0x00 => Kb32,
             0x01 => Kb64,
             0x02 => Kb128,
@@ -197,13 +401,36 @@ impl From<Word> for RomSize {
             0x52 => Mb1_1,
             0x53 => Mb1_2,
             0x54 => Mb1_5,
-            _ => unreachable!(),
-        }
+            _ => return Err(ParsingError::InvalidRomSize { value }),
+        };
+        Ok(size)
+    }
+}
+
+impl RomSize {
+    /// Return the number of bytes of rom
+    pub fn bytes(self) -> usize {
+        use self::RomSize::*;
+        let banks = match self {
+            Kb32 => 2,
+            Kb64 => 4,
+            Kb128 => 8,
+            Kb256 => 16,
+            Kb512 => 32,
+            Mb1 => 64,
+            Mb2 => 128,
+            Mb4 => 256,
+            Mb8 => 512,
+            Mb1_1 => 72,
+            Mb1_2 => 80,
+            Mb1_5 => 96,
+        };
+        banks * 16 * 1024
     }
 }
 
 /// A cartridge RAM size flag value
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RamSize {
     None,
     Kb2,
@@ -213,37 +440,212 @@ pub enum RamSize {
     Kb64,
 }
 
-// TODO: change this to try_from once the `TryFrom` trait is stabilized
-impl From<Word> for RamSize {
-    fn from(value: Word) -> Self {
+impl TryFrom<Word> for RamSize {
+    type Error = ParsingError;
+
+    fn try_from(value: Word) -> Result<Self, ParsingError> {
         use self::RamSize::*;
-        match value {
+        let size = match value {
             0x00 => RamSize::None,
             0x01 => Kb2,
             0x02 => Kb8,
             0x03 => Kb32,
             0x04 => Kb128,
             0x05 => Kb64,
-            _ => unreachable!(),
+            _ => return Err(ParsingError::InvalidRamSize { value }),
+        };
+        Ok(size)
+    }
+}
+
+impl RamSize {
+    /// Return the number of bytes of external ram
+    pub fn bytes(self) -> usize {
+        use self::RamSize::*;
+        match self {
+            RamSize::None => 0,
+            Kb2 => 2 * 1024,
+            Kb8 => 8 * 1024,
+            Kb32 => 32 * 1024,
+            Kb128 => 128 * 1024,
+            Kb64 => 64 * 1024,
         }
     }
 }
 
 /// A cartridge desintation flag value
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Destination {
     Japan,
     NotJapan,
 }
 
-// TODO: change this to try_from once the `TryFrom` trait is stabilized
-impl From<Word> for Destination {
-    fn from(value: Word) -> Self {
+impl TryFrom<Word> for Destination {
+    type Error = ParsingError;
+
+    fn try_from(value: Word) -> Result<Self, ParsingError> {
         use self::Destination::*;
-        match value {
+        let destination = match value {
             0x00 => Japan,
             0x01 => NotJapan,
-            _ => unreachable!(),
+            _ => return Err(ParsingError::InvalidDestination { value }),
+        };
+        Ok(destination)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn header_bytes(title: &[u8], cgb_flag: Word) -> Vec<u8> {
+        let mut bytes = vec![0; 0x150];
+        bytes[TITLE_OFFSET as usize..TITLE_OFFSET as usize + title.len()].copy_from_slice(title);
+        bytes[CGB_FLAG_ADDRESS as usize] = cgb_flag;
+        bytes
+    }
+
+    #[test]
+    fn decodes_fields() {
+        let mut bytes = header_bytes(b"TETRIS", 0x00);
+        bytes[OLD_LICENSEE_CODE_ADDRESS as usize] = 0x01;
+        bytes[DESTINATION_CODE_ADDRESS as usize] = 0x01;
+        bytes[MASK_ROM_VERSION_ADDRESS as usize] = 0x01;
+        bytes[GLOBAL_CHECKSUM_OFFSET as usize] = 0x16;
+        bytes[GLOBAL_CHECKSUM_END as usize] = 0xBF;
+
+        let header = CartridgeHeader::try_parse_bytes(&bytes).unwrap();
+        assert_eq!(header.title, "TETRIS");
+        assert_eq!(header.cgb_support, CgbSupport::None);
+        assert_eq!(header.licensee.publisher(), Some("Nintendo"));
+        assert_eq!(header.kind, CartridgeKind::RomOnly);
+        assert_eq!(header.destination, Destination::NotJapan);
+        assert_eq!(header.version, 0x01);
+        assert_eq!(header.global_checksum, 0x16BF);
+        assert!(CartridgeHeader::try_parse_bytes(&bytes[..0x14F]).is_err());
+    }
+
+    #[test]
+    fn decodes_color_cartridges() {
+        let mut bytes = header_bytes(b"POKEMON_SLVAAXE", 0x80);
+        bytes[OLD_LICENSEE_CODE_ADDRESS as usize] = USE_NEW_LICENSEE_CODE;
+        bytes[NEW_LICENSEE_CODE_OFFSET as usize] = b'0';
+        bytes[NEW_LICENSEE_CODE_END as usize] = b'1';
+        bytes[SGB_FLAG_ADDRESS as usize] = SGB_SUPPORT_VALUE;
+
+        let header = CartridgeHeader::try_parse_bytes(&bytes).unwrap();
+        assert_eq!(header.title, "POKEMON_SLV");
+        assert_eq!(header.manufacturer_code, Some("AAXE".to_owned()));
+        assert_eq!(header.cgb_support, CgbSupport::Compatible);
+        assert!(header.sgb_support);
+        assert_eq!(header.licensee, Licensee::New(*b"01"));
+
+        // older color cartridges have a longer title and no manufacturer code
+        let bytes = header_bytes(b"ZELDA ORACLE", 0xC0);
+        let header = CartridgeHeader::try_parse_bytes(&bytes).unwrap();
+        assert_eq!(header.title, "ZELDA ORACLE");
+        assert_eq!(header.manufacturer_code, None);
+        assert_eq!(header.cgb_support, CgbSupport::Only);
+    }
+
+    #[test]
+    fn rejects_unknown_bytes() {
+        let mut bytes = header_bytes(b"HOMEBREW", 0x00);
+        bytes[RAM_SIZE_ADDRESS as usize] = 0x06;
+        let error = CartridgeHeader::try_parse_bytes(&bytes).unwrap_err();
+        assert_eq!(error.to_string(), "Ram size invalid byte 0x06");
+
+        assert!(CartridgeKind::try_from(0x04).is_err());
+        assert!(RomSize::try_from(0x09).is_err());
+        assert!(Destination::try_from(0x02).is_err());
+    }
+
+    #[test]
+    fn decodes_every_known_code() {
+        use self::CartridgeKind::*;
+        let kinds = [
+            (0x00, RomOnly),
+            (0x01, MBC1),
+            (0x02, MBC1Ram),
+            (0x03, MBC1RamBattery),
+            (0x05, MBC2),
+            (0x06, MBC2Battery),
+            (0x08, RomRam),
+            (0x09, RomRamBattery),
+            (0x0B, MMM01),
+            (0x0C, MMM01Ram),
+            (0x0D, MMM01RamBattery),
+            (0x0F, MBC3TimerBattery),
+            (0x10, MBC3TimerRamBattery),
+            (0x11, MBC3),
+            (0x12, MBC3Ram),
+            (0x13, MBC3RamBattery),
+            (0x19, MBC5),
+            (0x1A, MBC5Ram),
+            (0x1B, MBC5RamBattery),
+            (0x1C, MBC5Rumble),
+            (0x1D, MBC5RumbleRam),
+            (0x1E, MBC5RumbleRamBattery),
+            (0x20, MBC6),
+            (0x22, MBC7SensorRumbleRamBattery),
+            (0xFC, PocketCamera),
+            (0xFD, BandaiTama5),
+            (0xFE, HuC3),
+            (0xFF, HuC1RamBattery),
+        ];
+        let rom_sizes = [
+            (0x00, RomSize::Kb32),
+            (0x01, RomSize::Kb64),
+            (0x02, RomSize::Kb128),
+            (0x03, RomSize::Kb256),
+            (0x04, RomSize::Kb512),
+            (0x05, RomSize::Mb1),
+            (0x06, RomSize::Mb2),
+            (0x07, RomSize::Mb4),
+            (0x08, RomSize::Mb8),
+            (0x52, RomSize::Mb1_1),
+            (0x53, RomSize::Mb1_2),
+            (0x54, RomSize::Mb1_5),
+        ];
+        let ram_sizes = [
+            (0x00, RamSize::None),
+            (0x01, RamSize::Kb2),
+            (0x02, RamSize::Kb8),
+            (0x03, RamSize::Kb32),
+            (0x04, RamSize::Kb128),
+            (0x05, RamSize::Kb64),
+        ];
+        let destinations = [(0x00, Destination::Japan), (0x01, Destination::NotJapan)];
+
+        fn lookup<T: Copy>(table: &[(Word, T)], value: Word) -> Option<T> {
+            table.iter().find(|entry| entry.0 == value).map(|entry| entry.1)
+        }
+
+        // every listed code decodes to its variant, and every other byte is rejected
+        for value in 0..=0xFF {
+            assert_eq!(CartridgeKind::try_from(value).ok(), lookup(&kinds, value));
+            assert_eq!(RomSize::try_from(value).ok(), lookup(&rom_sizes, value));
+            assert_eq!(RamSize::try_from(value).ok(), lookup(&ram_sizes, value));
+            assert_eq!(Destination::try_from(value).ok(), lookup(&destinations, value));
         }
     }
+
+    #[test]
+    fn fixed_checksums_verify() {
+        let mut bytes = header_bytes(b"HOMEBREW", 0x00);
+        bytes[NINTENDO_LOGO_OFFSET as usize..NINTENDO_LOGO_END as usize + 1]
+            .copy_from_slice(&NINTENDO_LOGO);
+        bytes.resize(0x8000, 0xFF);
+
+        let verification = Verification::try_from_bytes(&bytes).unwrap();
+        assert!(verification.logo);
+        assert!(!verification.header_checksum);
+        assert!(!verification.global_checksum);
+
+        fix_checksums(&mut bytes).unwrap();
+        assert!(Verification::try_from_bytes(&bytes).unwrap().is_valid());
+
+        bytes[NINTENDO_LOGO_OFFSET as usize] = 0x00;
+        assert!(!Verification::try_from_bytes(&bytes).unwrap().logo);
+    }
 }
@@ -0,0 +1,228 @@
+// Copyright 2018 Will Johnston
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! HuC3 real time clock
+//!
+//! The clock is driven through a command register. Each command is a nibble with a nibble
+//! argument, and works on a 256 nibble memory where the current time is copied in and out.
+
+use hardware::cpu::CYCLES_PER_SECOND;
+use isa::Word;
+
+/// Read the nibble at the access index into the response, then advance the index
+const COMMAND_READ: Word = 0x1;
+
+/// Write the argument to the access index, then advance the index
+const COMMAND_WRITE: Word = 0x3;
+
+/// Set the lower nibble of the access index
+const COMMAND_INDEX_LOW: Word = 0x4;
+
+/// Set the upper nibble of the access index
+const COMMAND_INDEX_HIGH: Word = 0x5;
+
+/// Run the extended command selected by the argument
+const COMMAND_EXTENDED: Word = 0x6;
+
+/// Copy the current time into clock memory
+const EXTENDED_LATCH: Word = 0x0;
+
+/// Set the current time from clock memory
+const EXTENDED_SET: Word = 0x1;
+
+/// Report the clock status
+const EXTENDED_STATUS: Word = 0x2;
+
+/// Play the tone selected in clock memory
+const EXTENDED_TONE: Word = 0xE;
+
+/// Clock memory index of the tone to play
+const TONE_INDEX: usize = 0x27;
+
+const MINUTES_PER_DAY: u16 = 24 * 60;
+
+const MEMORY_SIZE: usize = 0x100;
+
+/// Number of bytes in a saved clock
+pub const SAVE_SIZE: usize = 4 + MEMORY_SIZE;
+
+/// A HuC3 real time clock, counting minutes from emulated cycles
+#[derive(Clone)]
+pub struct HuC3Clock {
+    /// Minutes since the start of the day
+    minutes: u16,
+    /// 12-bit day counter
+    days: u16,
+    /// Cycles counted towards the next minute
+    cycles: usize,
+    memory: [Word; MEMORY_SIZE],
+    index: u8,
+    /// Last command in the upper nibble and its result in the lower nibble
+    response: Word,
+}
+
+impl HuC3Clock {
+    /// Emulate the clock over a given number of cycles
+    pub fn emulate(&mut self, cycles: usize) {
+        self.cycles += cycles;
+        while self.cycles >= CYCLES_PER_SECOND * 60 {
+            self.cycles -= CYCLES_PER_SECOND * 60;
+            self.minutes += 1;
+            if self.minutes == MINUTES_PER_DAY {
+                self.minutes = 0;
+                self.days = (self.days + 1) & 0xFFF;
+            }
+        }
+    }
+
+    /// Run a command. Returns the tone to play if the command asked for one
+    pub fn command(&mut self, value: Word) -> Option<Word> {
+        let command = (value >> 4) & 0x07;
+        let argument = value & 0x0F;
+        let index = usize::from(self.index);
+        let mut result = 0;
+        let mut tone = None;
+
+        match command {
+            COMMAND_READ => {
+                result = self.memory[index];
+                self.index = self.index.wrapping_add(1);
+            }
+            COMMAND_WRITE => {
+                self.memory[index] = argument;
+                self.index = self.index.wrapping_add(1);
+            }
+            COMMAND_INDEX_LOW => self.index = (self.index & 0xF0) | argument,
+            COMMAND_INDEX_HIGH => self.index = (self.index & 0x0F) | (argument << 4),
+            COMMAND_EXTENDED => match argument {
+                EXTENDED_LATCH => self.latch(),
+                EXTENDED_SET => self.set(),
+                EXTENDED_STATUS => result = 0x1,
+                EXTENDED_TONE => tone = Some(self.memory[TONE_INDEX]),
+                _ => debug!("Unknown HuC3 extended command {:x}", argument),
+            },
+            _ => debug!("Unknown HuC3 command {:x}", command),
+        }
+
+        self.response = (command << 4) | result;
+        tone
+    }
+
+    /// Return the response to the last command
+    pub fn response(&self) -> Word {
+        0x80 | self.response
+    }
+
+    /// Copy the minutes and days, lowest nibble first, to the start of clock memory
+    fn latch(&mut self) {
+        for i in 0..3 {
+            self.memory[i] = (self.minutes >> (i * 4)) as Word & 0x0F;
+            self.memory[i + 3] = (self.days >> (i * 4)) as Word & 0x0F;
+        }
+    }
+
+    /// Set the minutes and days from the start of clock memory
+    fn set(&mut self) {
+        let read = |memory: &[Word]| {
+            memory
+                .iter()
+                .rev()
+                .fold(0, |value, &nibble| (value << 4) | u16::from(nibble))
+        };
+        self.minutes = read(&self.memory[0..3]) % MINUTES_PER_DAY;
+        self.days = read(&self.memory[3..6]);
+        self.cycles = 0;
+    }
+
+    /// Serialize the clock as the minutes and days in little endian, followed by clock memory
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(SAVE_SIZE);
+        bytes.extend_from_slice(&[self.minutes as u8, (self.minutes >> 8) as u8]);
+        bytes.extend_from_slice(&[self.days as u8, (self.days >> 8) as u8]);
+        bytes.extend_from_slice(&self.memory);
+        bytes
+    }
+
+    /// Restore a clock serialized by `to_bytes`. Returns `None` if there are too few bytes
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < SAVE_SIZE {
+            return None;
+        }
+
+        let mut clock = Self::default();
+        clock.minutes = (u16::from(bytes[0]) | u16::from(bytes[1]) << 8) % MINUTES_PER_DAY;
+        clock.days = (u16::from(bytes[2]) | u16::from(bytes[3]) << 8) & 0xFFF;
+        for (nibble, &byte) in clock.memory.iter_mut().zip(&bytes[4..SAVE_SIZE]) {
+            *nibble = byte & 0x0F;
+        }
+        Some(clock)
+    }
+}
+
+impl Default for HuC3Clock {
+    fn default() -> Self {
+        HuC3Clock {
+            minutes: 0,
+            days: 0,
+            cycles: 0,
+            memory: [0; MEMORY_SIZE],
+            index: 0,
+            response: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Read the latched time through the command interface
+    fn read_time(clock: &mut HuC3Clock) -> Vec<Word> {
+        clock.command(0x60);
+        clock.command(0x40);
+        clock.command(0x50);
+        (0..6)
+            .map(|_| {
+                clock.command(0x10);
+                clock.response() & 0x0F
+            })
+            .collect()
+    }
+
+    #[test]
+    fn counts_minutes_and_days() {
+        let mut clock = HuC3Clock::default();
+        clock.emulate(CYCLES_PER_SECOND * 60 * (MINUTES_PER_DAY as usize + 0x12));
+        assert_eq!(read_time(&mut clock), vec![0x2, 0x1, 0x0, 0x1, 0x0, 0x0]);
+    }
+
+    #[test]
+    fn sets_time_from_memory() {
+        let mut clock = HuC3Clock::default();
+        clock.command(0x40);
+        clock.command(0x50);
+        for &nibble in &[0xB, 0x3, 0x0, 0x5, 0x0, 0x0] {
+            clock.command(0x30 | nibble);
+        }
+        clock.command(0x61);
+        assert_eq!(clock.response(), 0xE0);
+
+        clock.command(0x62);
+        assert_eq!(clock.response(), 0xE1);
+        assert_eq!(read_time(&mut clock), vec![0xB, 0x3, 0x0, 0x5, 0x0, 0x0]);
+    }
+
+    #[test]
+    fn round_trips_through_bytes() {
+        let mut clock = HuC3Clock::default();
+        clock.emulate(CYCLES_PER_SECOND * 60 * 100);
+        let restored = HuC3Clock::from_bytes(&clock.to_bytes()).unwrap();
+        assert_eq!(restored.to_bytes(), clock.to_bytes());
+        assert!(HuC3Clock::from_bytes(&[0; 3]).is_none());
+    }
+}
@@ -0,0 +1,61 @@
+// Copyright 2018 Will Johnston
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Infrared ports on cartridges
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+/// A shared infrared transceiver a cartridge sends and receives light through
+pub type InfraredPort = Rc<RefCell<dyn Infrared>>;
+
+/// An infrared transceiver
+pub trait Infrared {
+    /// Turn the transmitting LED on or off
+    fn transmit(&mut self, on: bool);
+
+    /// Returns true if light is being received
+    fn receive(&self) -> bool;
+}
+
+/// One end of a pair of linked infrared transceivers. Light sent from one end is received by
+/// the other
+#[derive(Debug, Clone, Default)]
+pub struct Link {
+    outgoing: Rc<Cell<bool>>,
+    incoming: Rc<Cell<bool>>,
+}
+
+impl Link {
+    /// Create two transceivers pointed at each other
+    pub fn pair() -> (Self, Self) {
+        let a = Rc::new(Cell::new(false));
+        let b = Rc::new(Cell::new(false));
+
+        (
+            Link {
+                outgoing: a.clone(),
+                incoming: b.clone(),
+            },
+            Link {
+                outgoing: b,
+                incoming: a,
+            },
+        )
+    }
+}
+
+impl Infrared for Link {
+    fn transmit(&mut self, on: bool) {
+        self.outgoing.set(on)
+    }
+
+    fn receive(&self) -> bool {
+        self.incoming.get()
+    }
+}
@@ -8,162 +8,1408 @@
 
 //! Memory bank controller types
 
+use std::convert::TryFrom;
 use std::fmt::{self, Debug};
+use std::rc::Rc;
 
 use failure::Error;
-use hardware::memory::{Memory, Memory32Kb, Switchable};
+use hardware::cartridge::camera::{Camera, ImagePort};
+use hardware::cartridge::header::{
+    CartridgeKind, RamSize, RomSize, CATRIDGE_TYPE_ADDRESS, NINTENDO_LOGO, NINTENDO_LOGO_OFFSET,
+    RAM_SIZE_ADDRESS, ROM_SIZE_ADDRESS,
+};
+use hardware::cartridge::eeprom::Eeprom;
+use hardware::cartridge::huc3::HuC3Clock;
+use hardware::cartridge::infrared::InfraredPort;
+use hardware::cartridge::rtc::{self, Rtc};
+use hardware::cartridge::speaker::SpeakerPort;
+use hardware::cartridge::tama5::Tama5Clock;
+use hardware::cartridge::ParsingError;
+use hardware::memory::addresses::memory_map::{
+    ERAM_OFFSET, ERAM_END, ROM0_END, ROM0_OFFSET, SROM_END, SROM_OFFSET,
+};
+use hardware::memory::{Memory, Switchable};
 use isa::{Address, Word};
+use system::Tilt;
+
+/// Writing a value with this lower nibble to the ram enable register enables external ram
+const RAM_ENABLE_VALUE: Word = 0x0A;
+
+/// Value read from external ram while it is disabled or missing
+const DISABLED_RAM_VALUE: Word = 0xFF;
+
+const RAM_ENABLE_OFFSET: Address = 0x0000;
+const RAM_ENABLE_END: Address = 0x1FFF;
+
+const ROM_BANK_OFFSET: Address = 0x2000;
+const ROM_BANK_END: Address = 0x3FFF;
+
+const RAM_BANK_OFFSET: Address = 0x4000;
+const RAM_BANK_END: Address = 0x5FFF;
+
+const BANKING_MODE_OFFSET: Address = 0x6000;
+const BANKING_MODE_END: Address = 0x7FFF;
+
+/// Size of a switchable rom bank
+const ROM_BANK_SIZE: usize = 0x4000;
+
+/// Size of a switchable external ram bank
+const RAM_BANK_SIZE: usize = 0x2000;
+
+/// Number of half-bytes of ram built into the MBC2
+const MBC2_RAM_SIZE: usize = 512;
+
+/// Address bit that selects the MBC2 rom bank register over the ram enable register
+const MBC2_REGISTER_SELECT: Address = 0x0100;
+
+/// End of the MBC5 register holding the lower 8 rom bank bits
+const MBC5_ROM_BANK_LOW_END: Address = 0x2FFF;
+
+/// Start of the MBC5 register holding the 9th rom bank bit
+const MBC5_ROM_BANK_HIGH_OFFSET: Address = 0x3000;
+
+/// Bit of the MBC5 ram bank register that drives the rumble motor
+const MBC5_RUMBLE_BIT: Word = 0x08;
+
+/// Value written to the ram enable register to map the infrared port
+const INFRARED_SELECT: Word = 0x0E;
+
+/// Value read from the infrared port when no light is received
+const INFRARED_IDLE_VALUE: Word = 0xC0;
+
+/// Bit the MMM01 menu sets to lock in a game, and to lock the banking mode
+const MMM01_MAP_BIT: Word = 0x40;
+
+/// HuC3 mode mapping external ram for reading only
+const HUC3_RAM_READ: Word = 0x0;
+
+/// HuC3 mode mapping external ram
+const HUC3_RAM: Word = 0xA;
+
+/// HuC3 mode mapping the clock command register
+const HUC3_CLOCK_COMMAND: Word = 0xB;
+
+/// HuC3 mode mapping the clock response register
+const HUC3_CLOCK_RESPONSE: Word = 0xC;
+
+/// HuC3 mode mapping the clock ready flag
+const HUC3_CLOCK_SEMAPHORE: Word = 0xD;
+
+/// HuC3 mode mapping the infrared port
+const HUC3_INFRARED: Word = 0xE;
+
+/// Value written to 0x4000-0x5FFF to enable the MBC7 registers, along with ram enable
+const MBC7_REGISTERS_ENABLE_VALUE: Word = 0x40;
+
+/// End of the MBC7 registers. The rest of external ram reads as 0xFF
+const MBC7_REGISTERS_END: Address = 0xAFFF;
+
+/// MBC7 register that erases the latched accelerometer values
+const MBC7_ERASE_LATCH: Address = 0x0;
+
+/// MBC7 register that latches the accelerometer
+const MBC7_LATCH: Address = 0x1;
+
+/// MBC7 register wired to the EEPROM pins
+const MBC7_EEPROM: Address = 0x8;
+
+/// Value written to the erase register to erase the latched accelerometer values
+const MBC7_ERASE_LATCH_VALUE: Word = 0x55;
+
+/// Value written to the latch register to latch the accelerometer
+const MBC7_LATCH_VALUE: Word = 0xAA;
+
+/// Accelerometer value of both axes once erased
+const ACCELEROMETER_ERASED: u16 = 0x8000;
+
+/// Accelerometer value of an axis when held flat
+const ACCELEROMETER_CENTER: f32 = 0x81D0 as f32;
+
+/// Change in accelerometer value for a full unit of tilt
+const ACCELEROMETER_ONE_G: f32 = 0x70 as f32;
+
+/// Pocket Camera ram bank register bit that maps the camera registers
+const CAMERA_REGISTERS_BIT: u8 = 0x10;
+
+/// Bytes of storage in a TAMA5
+const TAMA5_RAM_SIZE: usize = 32;
+
+/// TAMA5 register holding the lower 4 bits of the rom bank number
+const TAMA5_ROM_BANK_LOW: usize = 0x0;
+
+/// TAMA5 register holding the 5th bit of the rom bank number
+const TAMA5_ROM_BANK_HIGH: usize = 0x1;
+
+/// TAMA5 register holding the lower nibble of a byte to write
+const TAMA5_WRITE_LOW: usize = 0x4;
+
+/// TAMA5 register holding the upper nibble of a byte to write
+const TAMA5_WRITE_HIGH: usize = 0x5;
+
+/// TAMA5 register holding the command in bits 1 to 3, and the 5th address bit in bit 0
+const TAMA5_COMMAND: usize = 0x6;
+
+/// TAMA5 register holding the lower 4 address bits. Writing it runs the command
+const TAMA5_ADDRESS_LOW: usize = 0x7;
+
+/// TAMA5 register that reads as ready
+const TAMA5_STATUS: usize = 0xA;
+
+/// TAMA5 register reading the lower nibble of the last byte read
+const TAMA5_READ_LOW: usize = 0xC;
+
+/// TAMA5 register reading the upper nibble of the last byte read
+const TAMA5_READ_HIGH: usize = 0xD;
+
+/// TAMA5 command writing a byte to storage
+const TAMA5_WRITE_STORAGE: Word = 0x0;
+
+/// TAMA5 command reading a byte from storage
+const TAMA5_READ_STORAGE: Word = 0x1;
+
+/// TAMA5 command writing a clock digit
+const TAMA5_WRITE_CLOCK: Word = 0x2;
+
+/// TAMA5 command reading a clock digit
+const TAMA5_READ_CLOCK: Word = 0x3;
+
+/// Upper bits of every value read from the TAMA5
+const TAMA5_READ_MASK: Word = 0xF0;
+
+/// Size of the menu at the end of a MMM01 rom
+const MMM01_MENU_SIZE: usize = 2 * ROM_BANK_SIZE;
+
+/// Smallest MMM01 rom, holding a 32KB game before the 32KB menu
+const MMM01_MIN_ROM_SIZE: usize = 2 * MMM01_MENU_SIZE;
+
+/// Size of a MBC1M multicart, which holds four 256KB games
+const MBC1_MULTICART_SIZE: usize = 0x10_0000;
+
+/// Convert a tilt along one axis into the value the MBC7 accelerometer latches
+fn accelerometer_value(tilt: f32) -> u16 {
+    (ACCELEROMETER_CENTER + tilt * ACCELEROMETER_ONE_G).clamp(0.0, f32::from(u16::MAX)) as u16
+}
+
+/// Returns true if a value written to a ram enable register enables ram
+fn is_ram_enable(value: Word) -> bool {
+    value & 0x0F == RAM_ENABLE_VALUE
+}
+
+/// Create the external ram described by the cartridge header
+fn new_ram(bytes: &[u8]) -> Result<Vec<Word>, ParsingError> {
+    let size = RamSize::try_from(bytes[RAM_SIZE_ADDRESS as usize])?;
+    Ok(vec![0; size.bytes()])
+}
+
+/// Check `mapper` can address all the banks `header` declares. The rom is checked to hold them
+/// when the cartridge is parsed
+fn validate_rom(
+    mapper: &'static str,
+    header: &[Word],
+    max_banks: usize,
+) -> Result<(), ParsingError> {
+    let size = RomSize::try_from(header[ROM_SIZE_ADDRESS as usize])?;
+    if size.bytes() > max_banks * ROM_BANK_SIZE {
+        return Err(ParsingError::RomTooLarge { size, mapper });
+    }
+    Ok(())
+}
+
+/// Read from a rom bank. Bank numbers past the end of the rom wrap around, as the unused
+/// bank lines aren't connected
+fn read_rom(rom: &[Word], bank: usize, address: Address) -> Word {
+    let banks = (rom.len() / ROM_BANK_SIZE).max(1);
+    let index = (bank % banks) * ROM_BANK_SIZE + usize::from(address) % ROM_BANK_SIZE;
+    rom.get(index).cloned().unwrap_or(DISABLED_RAM_VALUE)
+}
+
+/// Return the index into external ram an address in a ram bank refers to, if there is any ram
+fn ram_index(ram: &[Word], bank: usize, address: Address) -> Option<usize> {
+    if ram.is_empty() {
+        None
+    } else {
+        let offset = usize::from(address - ERAM_OFFSET) % RAM_BANK_SIZE;
+        Some((bank * RAM_BANK_SIZE + offset) % ram.len())
+    }
+}
+
+/// Read from an external ram bank
+fn read_ram(ram: &[Word], bank: usize, address: Address) -> Word {
+    ram_index(ram, bank, address).map_or(DISABLED_RAM_VALUE, |index| ram[index])
+}
+
+/// Write to an external ram bank. Returns true if the ram changed
+fn write_ram(ram: &mut [Word], bank: usize, address: Address, value: Word) -> bool {
+    ram_index(ram, bank, address).map_or(false, |index| store(&mut ram[index], value))
+}
+
+/// Store a value in battery backable storage. Returns true if the storage changed
+fn store(cell: &mut Word, value: Word) -> bool {
+    let changed = *cell != value;
+    *cell = value;
+    changed
+}
+
+/// MBC1M multicarts are 1MB and repeat the nintendo logo at the start of every game
+fn is_mbc1_multicart(bytes: &[u8]) -> bool {
+    let logo = usize::from(NINTENDO_LOGO_OFFSET);
+    let second_game = logo + 0x10 * ROM_BANK_SIZE;
+    bytes.len() == MBC1_MULTICART_SIZE
+        && bytes[second_game..second_game + NINTENDO_LOGO.len()] == NINTENDO_LOGO[..]
+}
 
 /// Memory bank controller
 #[derive(Clone)]
 pub enum Mbc {
     HuC1 {
-        // TODO: add HuC1 fields
+        rom: Rc<[Word]>,
+        ram: Vec<Word>,
+        /// When set, 0xA000-0xBFFF is the infrared port instead of ram
+        infrared_mode: bool,
+        rom_bank: u8,
+        ram_bank: u8,
+        infrared: Option<InfraredPort>,
     },
     Mbc1 {
-        // TODO: add MBC1 fields
+        rom: Rc<[Word]>,
+        ram: Vec<Word>,
+        ram_enabled: bool,
+        /// Lower 5 bits of the rom bank number
+        bank1: u8,
+        /// Upper 2 bits of the rom bank number, or the ram bank number
+        bank2: u8,
+        /// When set, `bank2` also applies to 0x0000-0x3FFF and external ram
+        advanced_banking: bool,
+        /// MBC1M multicarts only wire 4 bits of `bank1`
+        multicart: bool,
     },
     Mbc2 {
-        // TODO: add MBC2 fields
+        rom: Rc<[Word]>,
+        /// Built in ram. Only the lower nibble of each byte is stored
+        ram: Vec<Word>,
+        ram_enabled: bool,
+        rom_bank: u8,
     },
     Mbc3 {
-        // TODO: add MBC3 fields
+        rom: Rc<[Word]>,
+        ram: Vec<Word>,
+        /// Enables both external ram and the clock
+        ram_enabled: bool,
+        rom_bank: u8,
+        /// Selects a ram bank, or a clock register from 0x08 to 0x0C
+        ram_bank: u8,
+        rtc: Option<Rtc>,
+        /// Last value written to the latch register. Writing 0x00 then 0x01 latches the clock
+        last_latch_write: Word,
     },
     Mbc5 {
-        // TODO: add MBC5 fields
+        rom: Rc<[Word]>,
+        ram: Vec<Word>,
+        ram_enabled: bool,
+        /// 9-bit rom bank number
+        rom_bank: u16,
+        ram_bank: u8,
+        /// Rumble cartridges wire bit 3 of the ram bank register to the motor
+        has_rumble: bool,
+        rumble: bool,
+    },
+    Mmm01 {
+        rom: Rc<[Word]>,
+        ram: Vec<Word>,
+        /// Cleared until the menu locks in a game. Until then the last 32KB of rom is mapped
+        mapped: bool,
+        ram_enabled: bool,
+        /// Lower 5 bits of the rom bank number
+        rom_bank_low: u8,
+        /// Bits 5 and 6 of the rom bank number, fixed once mapped
+        rom_bank_mid: u8,
+        /// Bits 7 and 8 of the rom bank number, fixed once mapped
+        rom_bank_high: u8,
+        /// Bits 1 to 4 of `rom_bank_low` that are fixed once mapped
+        rom_bank_mask: u8,
+        ram_bank_low: u8,
+        /// Bits 2 and 3 of the ram bank number, fixed once mapped
+        ram_bank_high: u8,
+        /// Bits of `ram_bank_low` that are fixed once mapped
+        ram_bank_mask: u8,
+        advanced_banking: bool,
+        /// When set, the banking mode can't be changed once mapped
+        banking_mode_locked: bool,
+    },
+    HuC3 {
+        rom: Rc<[Word]>,
+        ram: Vec<Word>,
+        /// Selects what 0xA000-0xBFFF maps to
+        mode: Word,
+        rom_bank: u8,
+        ram_bank: u8,
+        clock: HuC3Clock,
+        infrared: Option<InfraredPort>,
+        speaker: Option<SpeakerPort>,
+    },
+    Mbc7 {
+        rom: Rc<[Word]>,
+        /// Set by writing 0x0A to 0x0000-0x1FFF
+        ram_enabled: bool,
+        /// Set by writing 0x40 to 0x4000-0x5FFF. Both enables are needed to reach the registers
+        registers_enabled: bool,
+        rom_bank: u8,
+        eeprom: Eeprom,
+        /// Current tilt of the cartridge, read when the accelerometer is latched
+        tilt: Tilt,
+        /// Latched accelerometer x and y values
+        accelerometer: (u16, u16),
+        /// Set by erasing the latched values, and cleared once they are latched again
+        latch_armed: bool,
+    },
+    PocketCamera {
+        rom: Rc<[Word]>,
+        ram: Vec<Word>,
+        /// Enables writes to ram. Reads are always enabled
+        ram_enabled: bool,
+        rom_bank: u8,
+        /// Selects a ram bank, or the camera registers when bit 4 is set
+        ram_bank: u8,
+        camera: Camera,
+    },
+    Tama5 {
+        rom: Rc<[Word]>,
+        /// 32 bytes of storage
+        ram: Vec<Word>,
+        /// Register selected through 0xA001
+        register: Word,
+        /// Nibble registers written through 0xA000
+        registers: [Word; 0x10],
+        /// Byte fetched by the last read command
+        read_value: Word,
+        clock: Tama5Clock,
     },
 }
 
 impl Mbc {
-    pub fn try_parse_bytes_huc1(bytes: &[u8]) -> Result<Self, Error> {
-        fn validate(bytes: &[u8]) -> Result<(), Error> {
-            // TODO: implement fixed validation
-            Ok(())
-        }
-
-        validate(bytes)?;
+    pub fn try_parse_bytes_huc1(bytes: &Rc<[Word]>) -> Result<Self, Error> {
+        validate_rom("HuC1", bytes, 64)?;
 
-        // TODO: implement parsing for huc1 cartridges
-        unimplemented!()
+        Ok(Mbc::HuC1 {
+            rom: bytes.clone(),
+            ram: new_ram(bytes)?,
+            infrared_mode: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            infrared: None,
+        })
     }
 
     fn read_huc1(&self, address: Address) -> Word {
-        // TODO:  implement me
-        unimplemented!()
+        match *self {
+            Mbc::HuC1 {
+                ref rom,
+                ref ram,
+                infrared_mode,
+                rom_bank,
+                ram_bank,
+                ref infrared,
+            } => match address {
+                ROM0_OFFSET...ROM0_END => read_rom(rom, 0, address),
+                SROM_OFFSET...SROM_END => read_rom(rom, usize::from(rom_bank), address),
+                ERAM_OFFSET...ERAM_END if infrared_mode => {
+                    let light = infrared
+                        .as_ref()
+                        .map_or(false, |port| port.borrow().receive());
+                    INFRARED_IDLE_VALUE | light as Word
+                }
+                ERAM_OFFSET...ERAM_END => read_ram(ram, usize::from(ram_bank), address),
+                _ => DISABLED_RAM_VALUE,
+            },
+            _ => unreachable!(),
+        }
     }
 
-    fn write_huc1(&mut self, address: Address, value: Word) {
-        // TODO:  implement me
-        unimplemented!()
+    fn write_huc1(&mut self, address: Address, value: Word) -> bool {
+        match *self {
+            Mbc::HuC1 {
+                ref mut ram,
+                ref mut infrared_mode,
+                ref mut rom_bank,
+                ref mut ram_bank,
+                ref infrared,
+                ..
+            } => match address {
+                RAM_ENABLE_OFFSET...RAM_ENABLE_END => *infrared_mode = value == INFRARED_SELECT,
+                ROM_BANK_OFFSET...ROM_BANK_END => {
+                    *rom_bank = match value & 0x3F {
+                        0 => 1,
+                        bank => bank,
+                    }
+                }
+                RAM_BANK_OFFSET...RAM_BANK_END => *ram_bank = value & 0x03,
+                ERAM_OFFSET...ERAM_END if *infrared_mode => {
+                    if let Some(ref port) = *infrared {
+                        port.borrow_mut().transmit(value & 0x01 != 0);
+                    }
+                }
+                ERAM_OFFSET...ERAM_END => {
+                    return write_ram(ram, usize::from(*ram_bank), address, value)
+                }
+                _ => {}
+            },
+            _ => unreachable!(),
+        }
+        false
     }
 
     fn switch_bank_huc1(&mut self, bank_idx: u8) {
-        unimplemented!()
+        self.write_huc1(ROM_BANK_OFFSET, bank_idx);
     }
 
-    pub fn try_parse_bytes_mbc1(bytes: &[u8]) -> Result<Self, Error> {
-        fn validate(bytes: &[u8]) -> Result<(), Error> {
-            // TODO: implement fixed validation
-            Ok(())
-        }
-
-        validate(bytes)?;
+    pub fn try_parse_bytes_mbc1(bytes: &Rc<[Word]>) -> Result<Self, Error> {
+        validate_rom("MBC1", bytes, 128)?;
 
-        // TODO: implement me
-        unimplemented!()
+        Ok(Mbc::Mbc1 {
+            rom: bytes.clone(),
+            ram: new_ram(bytes)?,
+            ram_enabled: false,
+            bank1: 0,
+            bank2: 0,
+            advanced_banking: false,
+            multicart: is_mbc1_multicart(bytes),
+        })
     }
 
     fn read_mbc1(&self, address: Address) -> Word {
-        // TODO:  implement me
-        unimplemented!()
+        match *self {
+            Mbc::Mbc1 {
+                ref rom,
+                ref ram,
+                ram_enabled,
+                bank1,
+                bank2,
+                advanced_banking,
+                multicart,
+            } => {
+                let bank1_bits = if multicart { 4 } else { 5 };
+                let upper = usize::from(bank2) << bank1_bits;
+                match address {
+                    ROM0_OFFSET...ROM0_END if advanced_banking => read_rom(rom, upper, address),
+                    ROM0_OFFSET...ROM0_END => read_rom(rom, 0, address),
+                    SROM_OFFSET...SROM_END => {
+                        // the zero check sees all 5 bits, even when fewer are wired
+                        let lower = if bank1 == 0 { 1 } else { bank1 };
+                        let lower = usize::from(lower) & ((1 << bank1_bits) - 1);
+                        read_rom(rom, upper | lower, address)
+                    }
+                    ERAM_OFFSET...ERAM_END if ram_enabled => {
+                        let bank = if advanced_banking { bank2 } else { 0 };
+                        read_ram(ram, usize::from(bank), address)
+                    }
+                    _ => DISABLED_RAM_VALUE,
+                }
+            }
+            _ => unreachable!(),
+        }
     }
 
-    fn write_mbc1(&mut self, address: Address, value: Word) {
-        // TODO:  implement me
-        unimplemented!()
+    fn write_mbc1(&mut self, address: Address, value: Word) -> bool {
+        match *self {
+            Mbc::Mbc1 {
+                ref mut ram,
+                ref mut ram_enabled,
+                ref mut bank1,
+                ref mut bank2,
+                ref mut advanced_banking,
+                ..
+            } => match address {
+                RAM_ENABLE_OFFSET...RAM_ENABLE_END => *ram_enabled = is_ram_enable(value),
+                ROM_BANK_OFFSET...ROM_BANK_END => *bank1 = value & 0x1F,
+                RAM_BANK_OFFSET...RAM_BANK_END => *bank2 = value & 0x03,
+                BANKING_MODE_OFFSET...BANKING_MODE_END => *advanced_banking = value & 0x01 != 0,
+                ERAM_OFFSET...ERAM_END if *ram_enabled => {
+                    let bank = if *advanced_banking { *bank2 } else { 0 };
+                    return write_ram(ram, usize::from(bank), address, value);
+                }
+                _ => {}
+            },
+            _ => unreachable!(),
+        }
+        false
     }
 
     fn switch_bank_mbc1(&mut self, bank_idx: u8) {
-        unimplemented!()
+        self.write_mbc1(ROM_BANK_OFFSET, bank_idx);
     }
 
-    pub fn try_parse_bytes_mbc2(bytes: &[u8]) -> Result<Self, Error> {
-        fn validate(bytes: &[u8]) -> Result<(), Error> {
-            // TODO: implement fixed validation
-            Ok(())
-        }
+    pub fn try_parse_bytes_mbc2(bytes: &Rc<[Word]>) -> Result<Self, Error> {
+        validate_rom("MBC2", bytes, 16)?;
 
-        validate(bytes)?;
-
-        // TODO: implement me
-        unimplemented!()
+        Ok(Mbc::Mbc2 {
+            rom: bytes.clone(),
+            ram: vec![0; MBC2_RAM_SIZE],
+            ram_enabled: false,
+            rom_bank: 1,
+        })
     }
 
     fn read_mbc2(&self, address: Address) -> Word {
-        // TODO:  implement me
-        unimplemented!()
+        match *self {
+            Mbc::Mbc2 {
+                ref rom,
+                ref ram,
+                ram_enabled,
+                rom_bank,
+            } => match address {
+                ROM0_OFFSET...ROM0_END => read_rom(rom, 0, address),
+                SROM_OFFSET...SROM_END => read_rom(rom, usize::from(rom_bank), address),
+                // the 512 half-bytes repeat through the whole external ram area, and the
+                // missing upper nibble reads as set
+                ERAM_OFFSET...ERAM_END if ram_enabled => {
+                    0xF0 | ram[usize::from(address) % MBC2_RAM_SIZE]
+                }
+                _ => DISABLED_RAM_VALUE,
+            },
+            _ => unreachable!(),
+        }
     }
 
-    fn write_mbc2(&mut self, address: Address, value: Word) {
-        // TODO:  implement me
-        unimplemented!()
+    fn write_mbc2(&mut self, address: Address, value: Word) -> bool {
+        match *self {
+            Mbc::Mbc2 {
+                ref mut ram,
+                ref mut ram_enabled,
+                ref mut rom_bank,
+                ..
+            } => match address {
+                // bit 8 of the address picks between the two registers
+                ROM0_OFFSET...ROM0_END if address & MBC2_REGISTER_SELECT != 0 => {
+                    *rom_bank = match value & 0x0F {
+                        0 => 1,
+                        bank => bank,
+                    }
+                }
+                ROM0_OFFSET...ROM0_END => *ram_enabled = is_ram_enable(value),
+                ERAM_OFFSET...ERAM_END if *ram_enabled => {
+                    return store(&mut ram[usize::from(address) % MBC2_RAM_SIZE], value & 0x0F);
+                }
+                _ => {}
+            },
+            _ => unreachable!(),
+        }
+        false
     }
 
     fn switch_bank_mbc2(&mut self, bank_idx: u8) {
-        unimplemented!()
+        self.write_mbc2(MBC2_REGISTER_SELECT, bank_idx);
     }
 
-    pub fn try_parse_bytes_mbc3(bytes: &[u8]) -> Result<Self, Error> {
-        fn validate(bytes: &[u8]) -> Result<(), Error> {
-            // TODO: implement fixed validation
-            Ok(())
-        }
+    pub fn try_parse_bytes_mbc3(bytes: &Rc<[Word]>) -> Result<Self, Error> {
+        validate_rom("MBC3", bytes, 128)?;
 
-        validate(bytes)?;
+        let kind = CartridgeKind::try_from(bytes[CATRIDGE_TYPE_ADDRESS as usize])?;
+        let rtc = if kind.has_timer() {
+            Some(Rtc::default())
+        } else {
+            None
+        };
 
-        // TODO: validate me
-        unimplemented!()
+        Ok(Mbc::Mbc3 {
+            rom: bytes.clone(),
+            ram: new_ram(bytes)?,
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            rtc,
+            last_latch_write: 0xFF,
+        })
     }
 
     fn read_mbc3(&self, address: Address) -> Word {
-        // TODO:  implement me
-        unimplemented!()
+        match *self {
+            Mbc::Mbc3 {
+                ref rom,
+                ref ram,
+                ram_enabled,
+                rom_bank,
+                ram_bank,
+                ref rtc,
+                ..
+            } => match address {
+                ROM0_OFFSET...ROM0_END => read_rom(rom, 0, address),
+                SROM_OFFSET...SROM_END => read_rom(rom, usize::from(rom_bank), address),
+                ERAM_OFFSET...ERAM_END if ram_enabled => match (ram_bank, rtc) {
+                    (rtc::SECONDS...rtc::DAYS_HIGH, &Some(ref rtc)) => rtc.read(ram_bank),
+                    (0x00...0x03, _) => read_ram(ram, usize::from(ram_bank), address),
+                    _ => DISABLED_RAM_VALUE,
+                },
+                _ => DISABLED_RAM_VALUE,
+            },
+            _ => unreachable!(),
+        }
     }
 
-    fn write_mbc3(&mut self, address: Address, value: Word) {
-        // TODO:  implement me
-        unimplemented!()
+    fn write_mbc3(&mut self, address: Address, value: Word) -> bool {
+        match *self {
+            Mbc::Mbc3 {
+                ref mut ram,
+                ref mut ram_enabled,
+                ref mut rom_bank,
+                ref mut ram_bank,
+                ref mut rtc,
+                ref mut last_latch_write,
+                ..
+            } => match address {
+                RAM_ENABLE_OFFSET...RAM_ENABLE_END => *ram_enabled = is_ram_enable(value),
+                ROM_BANK_OFFSET...ROM_BANK_END => {
+                    *rom_bank = match value & 0x7F {
+                        0 => 1,
+                        bank => bank,
+                    }
+                }
+                RAM_BANK_OFFSET...RAM_BANK_END => *ram_bank = value,
+                BANKING_MODE_OFFSET...BANKING_MODE_END => {
+                    if *last_latch_write == 0x00 && value == 0x01 {
+                        if let Some(ref mut rtc) = *rtc {
+                            rtc.latch();
+                        }
+                    }
+                    *last_latch_write = value;
+                }
+                ERAM_OFFSET...ERAM_END if *ram_enabled => match (*ram_bank, rtc) {
+                    (rtc::SECONDS...rtc::DAYS_HIGH, &mut Some(ref mut rtc)) => {
+                        rtc.write(*ram_bank, value);
+                        return true;
+                    }
+                    (0x00...0x03, _) => {
+                        return write_ram(ram, usize::from(*ram_bank), address, value)
+                    }
+                    _ => {}
+                },
+                _ => {}
+            },
+            _ => unreachable!(),
+        }
+        false
     }
 
     fn switch_bank_mbc3(&mut self, bank_idx: u8) {
-        unimplemented!()
+        self.write_mbc3(ROM_BANK_OFFSET, bank_idx);
     }
 
-    pub fn try_parse_bytes_mbc5(bytes: &[u8]) -> Result<Self, Error> {
-        fn validate(bytes: &[u8]) -> Result<(), Error> {
-            // TODO: implement fixed validation
-            Ok(())
-        }
+    pub fn try_parse_bytes_mbc5(bytes: &Rc<[Word]>) -> Result<Self, Error> {
+        validate_rom("MBC5", bytes, 512)?;
 
-        validate(bytes)?;
+        let kind = CartridgeKind::try_from(bytes[CATRIDGE_TYPE_ADDRESS as usize])?;
 
-        // TODO: implement me
-        unimplemented!()
+        Ok(Mbc::Mbc5 {
+            rom: bytes.clone(),
+            ram: new_ram(bytes)?,
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            has_rumble: kind.has_rumble(),
+            rumble: false,
+        })
     }
 
     fn read_mbc5(&self, address: Address) -> Word {
-        // TODO:  implement me
-        unimplemented!()
+        match *self {
+            Mbc::Mbc5 {
+                ref rom,
+                ref ram,
+                ram_enabled,
+                rom_bank,
+                ram_bank,
+                ..
+            } => match address {
+                ROM0_OFFSET...ROM0_END => read_rom(rom, 0, address),
+                SROM_OFFSET...SROM_END => read_rom(rom, usize::from(rom_bank), address),
+                ERAM_OFFSET...ERAM_END if ram_enabled => {
+                    read_ram(ram, usize::from(ram_bank), address)
+                }
+                _ => DISABLED_RAM_VALUE,
+            },
+            _ => unreachable!(),
+        }
     }
 
-    fn write_mbc5(&mut self, address: Address, value: Word) {
-        // TODO:  implement me
-        unimplemented!()
+    fn write_mbc5(&mut self, address: Address, value: Word) -> bool {
+        match *self {
+            Mbc::Mbc5 {
+                ref mut ram,
+                ref mut ram_enabled,
+                ref mut rom_bank,
+                ref mut ram_bank,
+                has_rumble,
+                ref mut rumble,
+                ..
+            } => match address {
+                // unlike older controllers, all 8 bits are checked
+                RAM_ENABLE_OFFSET...RAM_ENABLE_END => *ram_enabled = value == RAM_ENABLE_VALUE,
+                ROM_BANK_OFFSET...MBC5_ROM_BANK_LOW_END => {
+                    *rom_bank = (*rom_bank & 0x100) | u16::from(value)
+                }
+                MBC5_ROM_BANK_HIGH_OFFSET...ROM_BANK_END => {
+                    *rom_bank = (*rom_bank & 0xFF) | (u16::from(value & 0x01) << 8)
+                }
+                RAM_BANK_OFFSET...RAM_BANK_END if has_rumble => {
+                    *rumble = value & MBC5_RUMBLE_BIT != 0;
+                    *ram_bank = value & 0x07;
+                }
+                RAM_BANK_OFFSET...RAM_BANK_END => *ram_bank = value & 0x0F,
+                ERAM_OFFSET...ERAM_END if *ram_enabled => {
+                    return write_ram(ram, usize::from(*ram_bank), address, value);
+                }
+                _ => {}
+            },
+            _ => unreachable!(),
+        }
+        false
     }
 
     fn switch_bank_mbc5(&mut self, bank_idx: u8) {
-        // TODO:  implement me
-        unimplemented!()
+        self.write_mbc5(ROM_BANK_OFFSET, bank_idx);
+    }
+
+    pub fn try_parse_bytes_mmm01(bytes: &Rc<[Word]>) -> Result<Self, Error> {
+        // the menu in the last 32KB boots first, so its header describes the cartridge rather
+        // than the header of the first game the rom was checked against. There has to be at
+        // least one game before it
+        if bytes.len() < MMM01_MIN_ROM_SIZE {
+            return Err(ParsingError::InvalidRomLength {
+                length: bytes.len(),
+                expected: MMM01_MIN_ROM_SIZE,
+            }.into());
+        }
+        let menu = &bytes[bytes.len() - MMM01_MENU_SIZE..];
+        validate_rom("MMM01", menu, 512)?;
+        let size = RomSize::try_from(menu[ROM_SIZE_ADDRESS as usize])?;
+        if bytes.len() < size.bytes() {
+            return Err(ParsingError::InvalidRomLength {
+                length: bytes.len(),
+                expected: size.bytes(),
+            }.into());
+        }
+
+        Ok(Mbc::Mmm01 {
+            rom: bytes.clone(),
+            ram: new_ram(menu)?,
+            mapped: false,
+            ram_enabled: false,
+            rom_bank_low: 0,
+            rom_bank_mid: 0,
+            rom_bank_high: 0,
+            rom_bank_mask: 0,
+            ram_bank_low: 0,
+            ram_bank_high: 0,
+            ram_bank_mask: 0,
+            advanced_banking: false,
+            banking_mode_locked: false,
+        })
+    }
+
+    fn read_mmm01(&self, address: Address) -> Word {
+        match *self {
+            Mbc::Mmm01 {
+                ref rom,
+                ref ram,
+                mapped,
+                ram_enabled,
+                rom_bank_low,
+                rom_bank_mid,
+                rom_bank_high,
+                rom_bank_mask,
+                ram_bank_low,
+                ram_bank_high,
+                advanced_banking,
+                ..
+            } => {
+                // the game's bank lines that the menu fixed in place
+                let outer = (usize::from(rom_bank_high) << 7) | (usize::from(rom_bank_mid) << 5);
+                let fixed = rom_bank_mask << 1;
+
+                match address {
+                    // every bank line is pulled high until mapped, selecting the last 32KB
+                    ROM0_OFFSET...ROM0_END if !mapped => read_rom(rom, 0x1FE, address),
+                    SROM_OFFSET...SROM_END if !mapped => read_rom(rom, 0x1FF, address),
+                    ROM0_OFFSET...ROM0_END => {
+                        read_rom(rom, outer | usize::from(rom_bank_low & fixed), address)
+                    }
+                    SROM_OFFSET...SROM_END => {
+                        // the zero check only sees the bits the game can change
+                        let low = if rom_bank_low & !fixed & 0x1F == 0 {
+                            rom_bank_low | 0x01
+                        } else {
+                            rom_bank_low
+                        };
+                        read_rom(rom, outer | usize::from(low), address)
+                    }
+                    ERAM_OFFSET...ERAM_END if ram_enabled => {
+                        let low = if advanced_banking { ram_bank_low } else { 0 };
+                        read_ram(ram, usize::from(ram_bank_high << 2 | low), address)
+                    }
+                    _ => DISABLED_RAM_VALUE,
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn write_mmm01(&mut self, address: Address, value: Word) -> bool {
+        match *self {
+            Mbc::Mmm01 {
+                ref mut ram,
+                ref mut mapped,
+                ref mut ram_enabled,
+                ref mut rom_bank_low,
+                ref mut rom_bank_mid,
+                ref mut rom_bank_high,
+                ref mut rom_bank_mask,
+                ref mut ram_bank_low,
+                ref mut ram_bank_high,
+                ref mut ram_bank_mask,
+                ref mut advanced_banking,
+                ref mut banking_mode_locked,
+                ..
+            } => match address {
+                RAM_ENABLE_OFFSET...RAM_ENABLE_END => {
+                    *ram_enabled = is_ram_enable(value);
+                    if !*mapped {
+                        *ram_bank_mask = (value >> 4) & 0x03;
+                        *mapped = value & MMM01_MAP_BIT != 0;
+                    }
+                }
+                ROM_BANK_OFFSET...ROM_BANK_END if *mapped => {
+                    let fixed = *rom_bank_mask << 1;
+                    *rom_bank_low = (*rom_bank_low & fixed) | (value & !fixed & 0x1F);
+                }
+                ROM_BANK_OFFSET...ROM_BANK_END => {
+                    *rom_bank_low = value & 0x1F;
+                    *rom_bank_mid = (value >> 5) & 0x03;
+                }
+                RAM_BANK_OFFSET...RAM_BANK_END if *mapped => {
+                    let fixed = *ram_bank_mask;
+                    *ram_bank_low = (*ram_bank_low & fixed) | (value & !fixed & 0x03);
+                }
+                RAM_BANK_OFFSET...RAM_BANK_END => {
+                    *ram_bank_low = value & 0x03;
+                    *ram_bank_high = (value >> 2) & 0x03;
+                    *rom_bank_high = (value >> 4) & 0x03;
+                    *banking_mode_locked = value & MMM01_MAP_BIT != 0;
+                }
+                BANKING_MODE_OFFSET...BANKING_MODE_END => {
+                    if !(*mapped && *banking_mode_locked) {
+                        *advanced_banking = value & 0x01 != 0;
+                    }
+                    if !*mapped {
+                        *rom_bank_mask = (value >> 2) & 0x0F;
+                    }
+                }
+                ERAM_OFFSET...ERAM_END if *ram_enabled => {
+                    let low = if *advanced_banking { *ram_bank_low } else { 0 };
+                    return write_ram(ram, usize::from(*ram_bank_high << 2 | low), address, value);
+                }
+                _ => {}
+            },
+            _ => unreachable!(),
+        }
+        false
+    }
+
+    fn switch_bank_mmm01(&mut self, bank_idx: u8) {
+        self.write_mmm01(ROM_BANK_OFFSET, bank_idx);
+    }
+
+    pub fn try_parse_bytes_huc3(bytes: &Rc<[Word]>) -> Result<Self, Error> {
+        validate_rom("HuC3", bytes, 128)?;
+
+        Ok(Mbc::HuC3 {
+            rom: bytes.clone(),
+            ram: new_ram(bytes)?,
+            mode: 0,
+            rom_bank: 1,
+            ram_bank: 0,
+            clock: HuC3Clock::default(),
+            infrared: None,
+            speaker: None,
+        })
+    }
+
+    fn read_huc3(&self, address: Address) -> Word {
+        match *self {
+            Mbc::HuC3 {
+                ref rom,
+                ref ram,
+                mode,
+                rom_bank,
+                ram_bank,
+                ref clock,
+                ref infrared,
+                ..
+            } => match address {
+                ROM0_OFFSET...ROM0_END => read_rom(rom, 0, address),
+                SROM_OFFSET...SROM_END => read_rom(rom, usize::from(rom_bank), address),
+                ERAM_OFFSET...ERAM_END => match mode {
+                    HUC3_RAM_READ | HUC3_RAM => read_ram(ram, usize::from(ram_bank), address),
+                    HUC3_CLOCK_RESPONSE => clock.response(),
+                    // commands run as soon as they're written, so the clock is always ready
+                    HUC3_CLOCK_SEMAPHORE => 0x01,
+                    HUC3_INFRARED => {
+                        let light = infrared
+                            .as_ref()
+                            .map_or(false, |port| port.borrow().receive());
+                        INFRARED_IDLE_VALUE | light as Word
+                    }
+                    _ => DISABLED_RAM_VALUE,
+                },
+                _ => DISABLED_RAM_VALUE,
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    fn write_huc3(&mut self, address: Address, value: Word) -> bool {
+        match *self {
+            Mbc::HuC3 {
+                ref mut ram,
+                ref mut mode,
+                ref mut rom_bank,
+                ref mut ram_bank,
+                ref mut clock,
+                ref infrared,
+                ref speaker,
+                ..
+            } => match address {
+                RAM_ENABLE_OFFSET...RAM_ENABLE_END => *mode = value & 0x0F,
+                ROM_BANK_OFFSET...ROM_BANK_END => {
+                    *rom_bank = match value & 0x7F {
+                        0 => 1,
+                        bank => bank,
+                    }
+                }
+                RAM_BANK_OFFSET...RAM_BANK_END => *ram_bank = value & 0x03,
+                ERAM_OFFSET...ERAM_END => match *mode {
+                    HUC3_RAM => return write_ram(ram, usize::from(*ram_bank), address, value),
+                    HUC3_CLOCK_COMMAND => {
+                        if let (Some(tone), &Some(ref speaker)) = (clock.command(value), speaker) {
+                            speaker.borrow_mut().play(tone);
+                        }
+                    }
+                    HUC3_INFRARED => {
+                        if let Some(ref port) = *infrared {
+                            port.borrow_mut().transmit(value & 0x01 != 0);
+                        }
+                    }
+                    _ => {}
+                },
+                _ => {}
+            },
+            _ => unreachable!(),
+        }
+        false
+    }
+
+    fn switch_bank_huc3(&mut self, bank_idx: u8) {
+        self.write_huc3(ROM_BANK_OFFSET, bank_idx);
+    }
+
+    pub fn try_parse_bytes_mbc7(bytes: &Rc<[Word]>) -> Result<Self, Error> {
+        validate_rom("MBC7", bytes, 128)?;
+
+        Ok(Mbc::Mbc7 {
+            rom: bytes.clone(),
+            ram_enabled: false,
+            registers_enabled: false,
+            rom_bank: 1,
+            eeprom: Eeprom::default(),
+            tilt: Tilt::default(),
+            accelerometer: (ACCELEROMETER_ERASED, ACCELEROMETER_ERASED),
+            latch_armed: false,
+        })
+    }
+
+    fn read_mbc7(&self, address: Address) -> Word {
+        match *self {
+            Mbc::Mbc7 {
+                ref rom,
+                ram_enabled,
+                registers_enabled,
+                rom_bank,
+                ref eeprom,
+                accelerometer: (x, y),
+                ..
+            } => match address {
+                ROM0_OFFSET...ROM0_END => read_rom(rom, 0, address),
+                SROM_OFFSET...SROM_END => read_rom(rom, usize::from(rom_bank), address),
+                ERAM_OFFSET...MBC7_REGISTERS_END if ram_enabled && registers_enabled => {
+                    match (address >> 4) & 0x0F {
+                        0x2 => x as Word,
+                        0x3 => (x >> 8) as Word,
+                        0x4 => y as Word,
+                        0x5 => (y >> 8) as Word,
+                        0x6 => 0x00,
+                        MBC7_EEPROM => eeprom.read_pins(),
+                        _ => DISABLED_RAM_VALUE,
+                    }
+                }
+                _ => DISABLED_RAM_VALUE,
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    fn write_mbc7(&mut self, address: Address, value: Word) -> bool {
+        match *self {
+            Mbc::Mbc7 {
+                ref mut ram_enabled,
+                ref mut registers_enabled,
+                ref mut rom_bank,
+                ref mut eeprom,
+                tilt,
+                ref mut accelerometer,
+                ref mut latch_armed,
+                ..
+            } => match address {
+                RAM_ENABLE_OFFSET...RAM_ENABLE_END => *ram_enabled = is_ram_enable(value),
+                ROM_BANK_OFFSET...ROM_BANK_END => *rom_bank = value & 0x7F,
+                RAM_BANK_OFFSET...RAM_BANK_END => {
+                    *registers_enabled = value == MBC7_REGISTERS_ENABLE_VALUE
+                }
+                ERAM_OFFSET...MBC7_REGISTERS_END if *ram_enabled && *registers_enabled => {
+                    match (address >> 4) & 0x0F {
+                        MBC7_ERASE_LATCH if value == MBC7_ERASE_LATCH_VALUE => {
+                            *accelerometer = (ACCELEROMETER_ERASED, ACCELEROMETER_ERASED);
+                            *latch_armed = true;
+                        }
+                        MBC7_LATCH if value == MBC7_LATCH_VALUE && *latch_armed => {
+                            *accelerometer =
+                                (accelerometer_value(tilt.x), accelerometer_value(tilt.y));
+                            *latch_armed = false;
+                        }
+                        MBC7_EEPROM => return eeprom.write_pins(value),
+                        _ => {}
+                    }
+                }
+                _ => {}
+            },
+            _ => unreachable!(),
+        }
+        false
+    }
+
+    fn switch_bank_mbc7(&mut self, bank_idx: u8) {
+        self.write_mbc7(ROM_BANK_OFFSET, bank_idx);
+    }
+
+    pub fn try_parse_bytes_pocket_camera(bytes: &Rc<[Word]>) -> Result<Self, Error> {
+        validate_rom("Pocket Camera", bytes, 64)?;
+
+        Ok(Mbc::PocketCamera {
+            rom: bytes.clone(),
+            ram: new_ram(bytes)?,
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            camera: Camera::default(),
+        })
+    }
+
+    fn read_pocket_camera(&self, address: Address) -> Word {
+        match *self {
+            Mbc::PocketCamera {
+                ref rom,
+                ref ram,
+                rom_bank,
+                ram_bank,
+                ref camera,
+                ..
+            } => match address {
+                ROM0_OFFSET...ROM0_END => read_rom(rom, 0, address),
+                SROM_OFFSET...SROM_END => read_rom(rom, usize::from(rom_bank), address),
+                ERAM_OFFSET...ERAM_END if ram_bank & CAMERA_REGISTERS_BIT != 0 => {
+                    camera.read(usize::from(address & 0x7F))
+                }
+                ERAM_OFFSET...ERAM_END => read_ram(ram, usize::from(ram_bank), address),
+                _ => DISABLED_RAM_VALUE,
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    fn write_pocket_camera(&mut self, address: Address, value: Word) -> bool {
+        match *self {
+            Mbc::PocketCamera {
+                ref mut ram,
+                ref mut ram_enabled,
+                ref mut rom_bank,
+                ref mut ram_bank,
+                ref mut camera,
+                ..
+            } => match address {
+                RAM_ENABLE_OFFSET...RAM_ENABLE_END => *ram_enabled = is_ram_enable(value),
+                ROM_BANK_OFFSET...ROM_BANK_END => *rom_bank = value & 0x3F,
+                RAM_BANK_OFFSET...RAM_BANK_END => *ram_bank = value & 0x1F,
+                ERAM_OFFSET...ERAM_END if *ram_bank & CAMERA_REGISTERS_BIT != 0 => {
+                    camera.write(usize::from(address & 0x7F), value)
+                }
+                // the sensor owns ram while it is capturing
+                ERAM_OFFSET...ERAM_END if *ram_enabled && !camera.is_capturing() => {
+                    return write_ram(ram, usize::from(*ram_bank), address, value);
+                }
+                _ => {}
+            },
+            _ => unreachable!(),
+        }
+        false
+    }
+
+    fn switch_bank_pocket_camera(&mut self, bank_idx: u8) {
+        self.write_pocket_camera(ROM_BANK_OFFSET, bank_idx);
+    }
+
+    pub fn try_parse_bytes_tama5(bytes: &Rc<[Word]>) -> Result<Self, Error> {
+        validate_rom("TAMA5", bytes, 32)?;
+
+        Ok(Mbc::Tama5 {
+            rom: bytes.clone(),
+            ram: vec![0; TAMA5_RAM_SIZE],
+            register: 0,
+            registers: [0; 0x10],
+            read_value: 0,
+            clock: Tama5Clock::default(),
+        })
+    }
+
+    fn read_tama5(&self, address: Address) -> Word {
+        match *self {
+            Mbc::Tama5 {
+                ref rom,
+                register,
+                ref registers,
+                read_value,
+                ..
+            } => match address {
+                ROM0_OFFSET...ROM0_END => read_rom(rom, 0, address),
+                SROM_OFFSET...SROM_END => {
+                    let bank = registers[TAMA5_ROM_BANK_HIGH] << 4 | registers[TAMA5_ROM_BANK_LOW];
+                    read_rom(rom, usize::from(bank), address)
+                }
+                // only the data register is readable, the register select reads as open bus
+                ERAM_OFFSET...ERAM_END if address & 0x01 == 0 => {
+                    TAMA5_READ_MASK | match usize::from(register) {
+                        TAMA5_STATUS => 0x1,
+                        TAMA5_READ_LOW => read_value & 0x0F,
+                        TAMA5_READ_HIGH => read_value >> 4,
+                        _ => 0x0,
+                    }
+                }
+                _ => DISABLED_RAM_VALUE,
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    fn write_tama5(&mut self, address: Address, value: Word) -> bool {
+        match *self {
+            Mbc::Tama5 {
+                ref mut ram,
+                ref mut register,
+                ref mut registers,
+                ref mut read_value,
+                ref mut clock,
+                ..
+            } => match address {
+                ERAM_OFFSET...ERAM_END if address & 0x01 != 0 => *register = value & 0x0F,
+                ERAM_OFFSET...ERAM_END => {
+                    let selected = usize::from(*register);
+                    registers[selected] = value & 0x0F;
+                    if selected != TAMA5_ADDRESS_LOW {
+                        return false;
+                    }
+
+                    let command = registers[TAMA5_COMMAND] >> 1;
+                    let index = (registers[TAMA5_COMMAND] & 0x01) << 4 | registers[selected];
+                    let data = registers[TAMA5_WRITE_HIGH] << 4 | registers[TAMA5_WRITE_LOW];
+                    match command {
+                        TAMA5_WRITE_STORAGE => return store(&mut ram[usize::from(index)], data),
+                        TAMA5_READ_STORAGE => *read_value = ram[usize::from(index)],
+                        TAMA5_WRITE_CLOCK => {
+                            clock.write(index, data);
+                            return true;
+                        }
+                        TAMA5_READ_CLOCK => *read_value = clock.read(index),
+                        _ => debug!("Unknown TAMA5 command {:x}", command),
+                    }
+                }
+                _ => {}
+            },
+            _ => unreachable!(),
+        }
+        false
+    }
+
+    fn switch_bank_tama5(&mut self, bank_idx: u8) {
+        if let Mbc::Tama5 {
+            ref mut registers, ..
+        } = *self
+        {
+            registers[TAMA5_ROM_BANK_LOW] = bank_idx & 0x0F;
+            registers[TAMA5_ROM_BANK_HIGH] = (bank_idx >> 4) & 0x01;
+        }
+    }
+}
+
+impl Mbc {
+    /// Return the battery backable ram of the controller, if it has any
+    pub fn ram(&self) -> Option<&[Word]> {
+        use self::Mbc::*;
+        match *self {
+            HuC1 { ref ram, .. }
+            | Mbc1 { ref ram, .. }
+            | Mbc2 { ref ram, .. }
+            | Mbc3 { ref ram, .. }
+            | Mbc5 { ref ram, .. }
+            | Mmm01 { ref ram, .. }
+            | HuC3 { ref ram, .. }
+            | PocketCamera { ref ram, .. }
+            | Tama5 { ref ram, .. }
+                if !ram.is_empty() =>
+            {
+                Some(ram)
+            }
+            Mbc7 { ref eeprom, .. } => Some(eeprom.data()),
+            _ => None,
+        }
+    }
+
+    /// Return the battery backable ram of the controller mutably, if it has any
+    pub fn ram_mut(&mut self) -> Option<&mut [Word]> {
+        use self::Mbc::*;
+        match *self {
+            HuC1 { ref mut ram, .. }
+            | Mbc1 { ref mut ram, .. }
+            | Mbc2 { ref mut ram, .. }
+            | Mbc3 { ref mut ram, .. }
+            | Mbc5 { ref mut ram, .. }
+            | Mmm01 { ref mut ram, .. }
+            | HuC3 { ref mut ram, .. }
+            | PocketCamera { ref mut ram, .. }
+            | Tama5 { ref mut ram, .. }
+                if !ram.is_empty() =>
+            {
+                Some(ram)
+            }
+            Mbc7 {
+                ref mut eeprom, ..
+            } => Some(eeprom.data_mut()),
+            _ => None,
+        }
+    }
+}
+
+impl Mbc {
+    /// Emulate any hardware on the controller that runs on its own, like clocks, over a given
+    /// number of cycles
+    pub fn emulate(&mut self, cycles: usize) {
+        match *self {
+            Mbc::Mbc3 {
+                rtc: Some(ref mut rtc),
+                ..
+            } => rtc.emulate(cycles),
+            Mbc::HuC3 { ref mut clock, .. } => clock.emulate(cycles),
+            Mbc::Tama5 { ref mut clock, .. } => clock.emulate(cycles),
+            Mbc::PocketCamera {
+                ref mut camera,
+                ref mut ram,
+                ..
+            } => camera.emulate(cycles, ram),
+            _ => {}
+        }
+    }
+
+    /// Connect an infrared transceiver to the controller's infrared port. Controllers without
+    /// one ignore it
+    pub fn connect_infrared(&mut self, port: InfraredPort) {
+        match *self {
+            Mbc::HuC1 {
+                ref mut infrared, ..
+            }
+            | Mbc::HuC3 {
+                ref mut infrared, ..
+            } => *infrared = Some(port),
+            _ => {}
+        }
+    }
+
+    /// Connect a tone generator to the controller's speaker. Controllers without one ignore it
+    pub fn connect_speaker(&mut self, port: SpeakerPort) {
+        if let Mbc::HuC3 { ref mut speaker, .. } = *self {
+            *speaker = Some(port)
+        }
+    }
+
+    /// Return the MBC3 real time clock, if the controller has one
+    pub fn rtc(&self) -> Option<&Rtc> {
+        match *self {
+            Mbc::Mbc3 { ref rtc, .. } => rtc.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Return the MBC3 real time clock mutably, if the controller has one
+    pub fn rtc_mut(&mut self) -> Option<&mut Rtc> {
+        match *self {
+            Mbc::Mbc3 { ref mut rtc, .. } => rtc.as_mut(),
+            _ => None,
+        }
+    }
+
+    /// Serialize the clock the controller keeps running, if it has one that isn't mapped
+    /// through its ram
+    pub fn save_clock(&self) -> Option<Vec<u8>> {
+        match *self {
+            Mbc::HuC3 { ref clock, .. } => Some(clock.to_bytes()),
+            Mbc::Tama5 { ref clock, .. } => Some(clock.to_bytes()),
+            _ => None,
+        }
+    }
+
+    /// Restore a clock serialized by `save_clock`. Returns false if the controller has no such
+    /// clock or the bytes don't hold one
+    pub fn load_clock(&mut self, bytes: &[u8]) -> bool {
+        match *self {
+            Mbc::HuC3 { ref mut clock, .. } => {
+                HuC3Clock::from_bytes(bytes).map(|saved| *clock = saved)
+            }
+            Mbc::Tama5 { ref mut clock, .. } => {
+                Tama5Clock::from_bytes(bytes).map(|saved| *clock = saved)
+            }
+            _ => None,
+        }.is_some()
+    }
+
+    /// Connect the image provider the controller's camera captures from. Controllers without
+    /// one ignore it
+    pub fn connect_camera(&mut self, port: ImagePort) {
+        if let Mbc::PocketCamera { ref mut camera, .. } = *self {
+            camera.connect(port)
+        }
+    }
+
+    /// Set the tilt the controller's accelerometer reads from. Controllers without one ignore it
+    pub fn set_tilt(&mut self, tilt: Tilt) {
+        if let Mbc::Mbc7 {
+            tilt: ref mut current,
+            ..
+        } = *self
+        {
+            *current = tilt
+        }
+    }
+
+    /// Returns true if the controller is driving a rumble motor
+    pub fn is_rumbling(&self) -> bool {
+        match *self {
+            Mbc::Mbc5 { rumble, .. } => rumble,
+            _ => false,
+        }
     }
 }
 
@@ -176,10 +1422,23 @@ impl Memory for Mbc {
             Mbc2 { .. } => self.read_mbc2(address),
             Mbc3 { .. } => self.read_mbc3(address),
             Mbc5 { .. } => self.read_mbc5(address),
+            Mmm01 { .. } => self.read_mmm01(address),
+            HuC3 { .. } => self.read_huc3(address),
+            Mbc7 { .. } => self.read_mbc7(address),
+            PocketCamera { .. } => self.read_pocket_camera(address),
+            Tama5 { .. } => self.read_tama5(address),
         }
     }
 
     fn write(&mut self, address: Address, value: Word) {
+        self.write_tracked(address, value);
+    }
+}
+
+impl Mbc {
+    /// Write to the controller. Returns true if the write changed battery backable ram or set a
+    /// clock, rather than a register or ram that is disabled
+    pub fn write_tracked(&mut self, address: Address, value: Word) -> bool {
         use self::Mbc::*;
         match *self {
             HuC1 { .. } => self.write_huc1(address, value),
@@ -187,6 +1446,11 @@ impl Memory for Mbc {
             Mbc2 { .. } => self.write_mbc2(address, value),
             Mbc3 { .. } => self.write_mbc3(address, value),
             Mbc5 { .. } => self.write_mbc5(address, value),
+            Mmm01 { .. } => self.write_mmm01(address, value),
+            HuC3 { .. } => self.write_huc3(address, value),
+            Mbc7 { .. } => self.write_mbc7(address, value),
+            PocketCamera { .. } => self.write_pocket_camera(address, value),
+            Tama5 { .. } => self.write_tama5(address, value),
         }
     }
 }
@@ -200,13 +1464,538 @@ impl Switchable for Mbc {
             Mbc2 { .. } => self.switch_bank_mbc2(bank_idx),
             Mbc3 { .. } => self.switch_bank_mbc3(bank_idx),
             Mbc5 { .. } => self.switch_bank_mbc5(bank_idx),
+            Mmm01 { .. } => self.switch_bank_mmm01(bank_idx),
+            HuC3 { .. } => self.switch_bank_huc3(bank_idx),
+            Mbc7 { .. } => self.switch_bank_mbc7(bank_idx),
+            PocketCamera { .. } => self.switch_bank_pocket_camera(bank_idx),
+            Tama5 { .. } => self.switch_bank_tama5(bank_idx),
         }
     }
 }
 
+/// Controllers print their kind, the size of their rom and ram, and their bank registers. The
+/// contents of rom and ram are left out
 impl Debug for Mbc {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        // TODO: implement debug printing for MBC
-        unimplemented!()
+        use self::Mbc::*;
+        let (name, rom, ram) = match *self {
+            HuC1 { ref rom, ref ram, .. } => ("HuC1", rom, ram.len()),
+            Mbc1 { ref rom, ref ram, .. } => ("Mbc1", rom, ram.len()),
+            Mbc2 { ref rom, ref ram, .. } => ("Mbc2", rom, ram.len()),
+            Mbc3 { ref rom, ref ram, .. } => ("Mbc3", rom, ram.len()),
+            Mbc5 { ref rom, ref ram, .. } => ("Mbc5", rom, ram.len()),
+            Mmm01 { ref rom, ref ram, .. } => ("Mmm01", rom, ram.len()),
+            HuC3 { ref rom, ref ram, .. } => ("HuC3", rom, ram.len()),
+            Mbc7 { ref rom, ref eeprom, .. } => ("Mbc7", rom, eeprom.data().len()),
+            PocketCamera { ref rom, ref ram, .. } => ("PocketCamera", rom, ram.len()),
+            Tama5 { ref rom, ref ram, .. } => ("Tama5", rom, ram.len()),
+        };
+
+        let mut debug = f.debug_struct(name);
+        debug.field("rom_size", &rom.len()).field("ram_size", &ram);
+        match *self {
+            HuC1 {
+                infrared_mode,
+                rom_bank,
+                ram_bank,
+                ..
+            } => debug
+                .field("infrared_mode", &infrared_mode)
+                .field("rom_bank", &rom_bank)
+                .field("ram_bank", &ram_bank),
+            Mbc1 {
+                ram_enabled,
+                bank1,
+                bank2,
+                advanced_banking,
+                multicart,
+                ..
+            } => debug
+                .field("ram_enabled", &ram_enabled)
+                .field("bank1", &bank1)
+                .field("bank2", &bank2)
+                .field("advanced_banking", &advanced_banking)
+                .field("multicart", &multicart),
+            Mbc2 {
+                ram_enabled,
+                rom_bank,
+                ..
+            } => debug
+                .field("ram_enabled", &ram_enabled)
+                .field("rom_bank", &rom_bank),
+            Mbc3 {
+                ram_enabled,
+                rom_bank,
+                ram_bank,
+                ref rtc,
+                ..
+            } => debug
+                .field("ram_enabled", &ram_enabled)
+                .field("rom_bank", &rom_bank)
+                .field("ram_bank", &ram_bank)
+                .field("rtc", rtc),
+            Mbc5 {
+                ram_enabled,
+                rom_bank,
+                ram_bank,
+                rumble,
+                ..
+            } => debug
+                .field("ram_enabled", &ram_enabled)
+                .field("rom_bank", &rom_bank)
+                .field("ram_bank", &ram_bank)
+                .field("rumble", &rumble),
+            Mmm01 {
+                mapped,
+                ram_enabled,
+                rom_bank_low,
+                rom_bank_mid,
+                rom_bank_high,
+                ram_bank_low,
+                ram_bank_high,
+                advanced_banking,
+                ..
+            } => debug
+                .field("mapped", &mapped)
+                .field("ram_enabled", &ram_enabled)
+                .field("rom_bank_low", &rom_bank_low)
+                .field("rom_bank_mid", &rom_bank_mid)
+                .field("rom_bank_high", &rom_bank_high)
+                .field("ram_bank_low", &ram_bank_low)
+                .field("ram_bank_high", &ram_bank_high)
+                .field("advanced_banking", &advanced_banking),
+            HuC3 {
+                mode,
+                rom_bank,
+                ram_bank,
+                ..
+            } => debug
+                .field("mode", &mode)
+                .field("rom_bank", &rom_bank)
+                .field("ram_bank", &ram_bank),
+            Mbc7 {
+                ram_enabled,
+                registers_enabled,
+                rom_bank,
+                accelerometer,
+                ..
+            } => debug
+                .field("ram_enabled", &ram_enabled)
+                .field("registers_enabled", &registers_enabled)
+                .field("rom_bank", &rom_bank)
+                .field("accelerometer", &accelerometer),
+            PocketCamera {
+                ram_enabled,
+                rom_bank,
+                ram_bank,
+                ..
+            } => debug
+                .field("ram_enabled", &ram_enabled)
+                .field("rom_bank", &rom_bank)
+                .field("ram_bank", &ram_bank),
+            Tama5 {
+                register,
+                ref registers,
+                ..
+            } => debug
+                .field("register", &register)
+                .field("registers", registers),
+        };
+        debug.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use hardware::cpu::CYCLES_PER_SECOND;
+
+    /// Create a rom where every bank starts with its own bank number
+    fn numbered_rom(banks: usize) -> Vec<u8> {
+        let mut rom = vec![0; banks * ROM_BANK_SIZE];
+        for bank in 0..banks {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+        }
+        rom
+    }
+
+    #[test]
+    fn rejects_roms_larger_than_the_controller_addresses() {
+        use hardware::cartridge::Cartridge;
+        use hardware::cartridge::header::CATRIDGE_TYPE_ADDRESS;
+
+        type Parser = fn(&Rc<[Word]>) -> Result<Mbc, Error>;
+        let controllers: &[(Word, Parser, usize)] = &[
+            (0x01, Mbc::try_parse_bytes_mbc1, 128),
+            (0x05, Mbc::try_parse_bytes_mbc2, 16),
+            (0x0F, Mbc::try_parse_bytes_mbc3, 128),
+            (0x19, Mbc::try_parse_bytes_mbc5, 512),
+            (0x0B, Mbc::try_parse_bytes_mmm01, 512),
+            (0x22, Mbc::try_parse_bytes_mbc7, 128),
+            (0xFC, Mbc::try_parse_bytes_pocket_camera, 64),
+            (0xFD, Mbc::try_parse_bytes_tama5, 32),
+            (0xFE, Mbc::try_parse_bytes_huc3, 128),
+            (0xFF, Mbc::try_parse_bytes_huc1, 64),
+        ];
+
+        for &(kind, parse, max_banks) in controllers {
+            // rom size codes declare two banks shifted left by the code, up to 512 banks
+            let code = max_banks.trailing_zeros() as Word;
+            let mut rom = numbered_rom(4);
+            rom[CATRIDGE_TYPE_ADDRESS as usize] = kind;
+            if max_banks < 512 {
+                // MMM01 roms are described by the header of the menu in their last 32KB
+                for &header in &[0, 2 * ROM_BANK_SIZE] {
+                    rom[header + ROM_SIZE_ADDRESS as usize] = code;
+                }
+                match parse(&rom.clone().into()).err().map(Error::downcast) {
+                    Some(Ok(ParsingError::RomTooLarge { .. })) => {}
+                    error => panic!("{:02X} accepted {} banks: {:?}", kind, max_banks * 2, error),
+                }
+            }
+
+            // the whole cartridge checks the rom holds every bank the header declares
+            rom[ROM_SIZE_ADDRESS as usize] = code - 1;
+            match Cartridge::try_parse_bytes(&rom).err().map(Error::downcast) {
+                Some(Ok(ParsingError::InvalidRomLength { length, expected })) => {
+                    assert_eq!((length, expected), (rom.len(), max_banks * ROM_BANK_SIZE))
+                }
+                error => panic!("{:02X} accepted a short rom: {:?}", kind, error),
+            }
+        }
+    }
+
+    #[test]
+    fn debug_prints_bank_registers() {
+        let mut mbc = Mbc::try_parse_bytes_mbc5(&numbered_rom(4).into()).unwrap();
+        mbc.write(0x2000, 0x03);
+        assert_eq!(
+            format!("{:?}", mbc),
+            "Mbc5 { rom_size: 65536, ram_size: 0, ram_enabled: false, rom_bank: 3, ram_bank: 0, \
+             rumble: false }"
+        );
+    }
+
+    #[test]
+    fn mbc1_bank_zero_selects_bank_one() {
+        let mut mbc = Mbc::try_parse_bytes_mbc1(&numbered_rom(64).into()).unwrap();
+        assert_eq!(mbc.read(0x4000), 1);
+
+        mbc.write(0x2000, 0x00);
+        assert_eq!(mbc.read(0x4000), 1);
+
+        mbc.write(0x2000, 0x05);
+        assert_eq!(mbc.read(0x4000), 5);
+
+        // the upper bits also apply and bank 0x20 is remapped to 0x21
+        mbc.write(0x4000, 0x01);
+        mbc.write(0x2000, 0x00);
+        assert_eq!(mbc.read(0x4000), 0x21);
+        assert_eq!(mbc.read(0x0000), 0);
+
+        mbc.write(0x6000, 0x01);
+        assert_eq!(mbc.read(0x0000), 0x20);
+    }
+
+    #[test]
+    fn mbc1_ram_requires_enable() {
+        let mut rom = numbered_rom(4);
+        rom[RAM_SIZE_ADDRESS as usize] = 0x03; // 32KB
+        let mut mbc = Mbc::try_parse_bytes_mbc1(&rom.into()).unwrap();
+
+        mbc.write(0xA000, 0x42);
+        assert_eq!(mbc.read(0xA000), 0xFF);
+
+        mbc.write(0x0000, 0x0A);
+        mbc.write(0xA000, 0x42);
+        assert_eq!(mbc.read(0xA000), 0x42);
+
+        // ram banks only switch in advanced banking mode
+        mbc.write(0x4000, 0x02);
+        assert_eq!(mbc.read(0xA000), 0x42);
+        mbc.write(0x6000, 0x01);
+        assert_eq!(mbc.read(0xA000), 0x00);
+
+        mbc.write(0x0000, 0x00);
+        assert_eq!(mbc.read(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn mbc2_registers_are_selected_by_address_bit_8() {
+        let mut mbc = Mbc::try_parse_bytes_mbc2(&numbered_rom(16).into()).unwrap();
+
+        // bit 8 clear is the ram enable register
+        mbc.write(0x2000, 0x03);
+        assert_eq!(mbc.read(0x4000), 1);
+
+        mbc.write(0x2100, 0x03);
+        assert_eq!(mbc.read(0x4000), 3);
+
+        mbc.write(0x3FFF, 0x10);
+        assert_eq!(mbc.read(0x4000), 1);
+    }
+
+    #[test]
+    fn mbc2_ram_is_half_bytes_and_echoed() {
+        let mut mbc = Mbc::try_parse_bytes_mbc2(&numbered_rom(16).into()).unwrap();
+        mbc.write(0xA000, 0x0C);
+        assert_eq!(mbc.read(0xA000), 0xFF);
+
+        mbc.write(0x0000, 0x0A);
+        mbc.write(0xA000, 0x5C);
+        assert_eq!(mbc.read(0xA000), 0xFC);
+        assert_eq!(mbc.read(0xA200), 0xFC);
+        assert_eq!(mbc.read(0xBE00), 0xFC);
+        assert_eq!(mbc.ram().map(|ram| ram.len()), Some(512));
+    }
+
+    #[test]
+    fn mbc3_latches_clock_registers() {
+        let mut rom = numbered_rom(128);
+        rom[CATRIDGE_TYPE_ADDRESS as usize] = 0x10; // MBC3+TIMER+RAM+BATTERY
+        rom[RAM_SIZE_ADDRESS as usize] = 0x03;
+        let mut mbc = Mbc::try_parse_bytes_mbc3(&rom.into()).unwrap();
+
+        mbc.write(0x2000, 0x7F);
+        assert_eq!(mbc.read(0x4000), 0x7F);
+
+        mbc.write(0x0000, 0x0A);
+        mbc.write(0x4000, rtc::SECONDS);
+        mbc.emulate(CYCLES_PER_SECOND * 5);
+        assert_eq!(mbc.read(0xA000), 0);
+
+        mbc.write(0x6000, 0x00);
+        mbc.write(0x6000, 0x01);
+        assert_eq!(mbc.read(0xA000), 5);
+
+        // ram banks are still reachable next to the clock
+        mbc.write(0x4000, 0x01);
+        mbc.write(0xA000, 0x42);
+        assert_eq!(mbc.read(0xA000), 0x42);
+    }
+
+    #[test]
+    fn mbc5_uses_9_bit_rom_bank() {
+        let mut rom = numbered_rom(512);
+        rom[0x1FF * ROM_BANK_SIZE + 1] = 0x42;
+        let mut mbc = Mbc::try_parse_bytes_mbc5(&rom.into()).unwrap();
+
+        mbc.write(0x2000, 0x00);
+        assert_eq!(mbc.read(0x4000), 0);
+
+        mbc.write(0x2000, 0xFF);
+        mbc.write(0x3000, 0x01);
+        assert_eq!(mbc.read(0x4001), 0x42);
+    }
+
+    #[test]
+    fn mbc5_rumble_bit_drives_motor() {
+        let mut rom = numbered_rom(4);
+        rom[CATRIDGE_TYPE_ADDRESS as usize] = 0x1D; // MBC5+RUMBLE+RAM
+        rom[RAM_SIZE_ADDRESS as usize] = 0x03;
+        let mut mbc = Mbc::try_parse_bytes_mbc5(&rom.into()).unwrap();
+        mbc.write(0x0000, 0x0A);
+        mbc.write(0xA000, 0x42);
+
+        mbc.write(0x4000, 0x08);
+        assert!(mbc.is_rumbling());
+        assert_eq!(mbc.read(0xA000), 0x42);
+
+        mbc.write(0x4000, 0x00);
+        assert!(!mbc.is_rumbling());
+    }
+
+    #[test]
+    fn huc1_infrared_mode_talks_through_port() {
+        use hardware::cartridge::infrared::{Infrared, Link};
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let mut rom = numbered_rom(4);
+        rom[RAM_SIZE_ADDRESS as usize] = 0x02;
+        let (near, mut far) = Link::pair();
+        let mut mbc = Mbc::try_parse_bytes_huc1(&rom.into()).unwrap();
+        mbc.connect_infrared(Rc::new(RefCell::new(near)));
+
+        mbc.write(0xA000, 0x42);
+        assert_eq!(mbc.read(0xA000), 0x42);
+
+        mbc.write(0x0000, 0x0E);
+        assert_eq!(mbc.read(0xA000), 0xC0);
+        far.transmit(true);
+        assert_eq!(mbc.read(0xA000), 0xC1);
+
+        mbc.write(0xA000, 0x01);
+        assert!(far.receive());
+
+        mbc.write(0x0000, 0x0A);
+        assert_eq!(mbc.read(0xA000), 0x42);
+    }
+
+    #[test]
+    fn mbc1_multicart_uses_four_bit_bank1() {
+        let mut rom = numbered_rom(64);
+        for game in 0..4 {
+            let logo = game * 0x10 * ROM_BANK_SIZE + NINTENDO_LOGO_OFFSET as usize;
+            rom[logo..logo + NINTENDO_LOGO.len()].copy_from_slice(&NINTENDO_LOGO);
+        }
+        let mut mbc = Mbc::try_parse_bytes_mbc1(&rom.into()).unwrap();
+
+        mbc.write(0x4000, 0x01);
+        mbc.write(0x2000, 0x12);
+        assert_eq!(mbc.read(0x4000), 0x12);
+
+        mbc.write(0x6000, 0x01);
+        assert_eq!(mbc.read(0x0000), 0x10);
+    }
+
+    #[test]
+    fn mmm01_boots_menu_then_maps_game() {
+        let mut mbc = Mbc::try_parse_bytes_mmm01(&numbered_rom(64).into()).unwrap();
+        assert_eq!(mbc.read(0x0000), 62);
+        assert_eq!(mbc.read(0x4000), 63);
+
+        // select the game starting at bank 0x20, fixing all but the lowest bank bit
+        mbc.write(0x2000, 0x20);
+        mbc.write(0x6000, 0x3C);
+        mbc.write(0x0000, 0x40);
+        assert_eq!(mbc.read(0x0000), 0x20);
+        assert_eq!(mbc.read(0x4000), 0x21);
+
+        // the game can't leave its own banks
+        mbc.write(0x2000, 0x1F);
+        assert_eq!(mbc.read(0x4000), 0x21);
+        mbc.write(0x2000, 0x00);
+        assert_eq!(mbc.read(0x4000), 0x21);
+    }
+
+    #[test]
+    fn mmm01_requires_a_menu_after_a_game() {
+        assert!(Mbc::try_parse_bytes_mmm01(&numbered_rom(2).into()).is_err());
+
+        // the menu header declares 256KB, more than the image holds
+        let mut rom = numbered_rom(8);
+        rom[6 * ROM_BANK_SIZE + ROM_SIZE_ADDRESS as usize] = 0x03;
+        assert!(Mbc::try_parse_bytes_mmm01(&rom.clone().into()).is_err());
+        rom[6 * ROM_BANK_SIZE + ROM_SIZE_ADDRESS as usize] = 0x01;
+        assert!(Mbc::try_parse_bytes_mmm01(&rom.into()).is_ok());
+    }
+
+    #[test]
+    fn huc3_runs_clock_commands() {
+        use hardware::cartridge::speaker::Speaker;
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        struct Tones(Vec<Word>);
+
+        impl Speaker for Tones {
+            fn play(&mut self, tone: Word) {
+                self.0.push(tone)
+            }
+        }
+
+        let mut mbc = Mbc::try_parse_bytes_huc3(&numbered_rom(4).into()).unwrap();
+        let tones = Rc::new(RefCell::new(Tones(Vec::new())));
+        mbc.connect_speaker(tones.clone());
+
+        mbc.write(0x0000, 0x0D);
+        assert_eq!(mbc.read(0xA000), 0x01);
+
+        mbc.write(0x0000, 0x0B);
+        mbc.write(0xA000, 0x62);
+        mbc.write(0x0000, 0x0C);
+        assert_eq!(mbc.read(0xA000), 0xE1);
+
+        // select the tone at 0x27, then play it
+        mbc.write(0x0000, 0x0B);
+        for &command in &[0x47, 0x52, 0x33, 0x6E] {
+            mbc.write(0xA000, command);
+        }
+        assert_eq!(tones.borrow().0, vec![0x3]);
+    }
+
+    #[test]
+    fn mbc7_latches_accelerometer() {
+        let mut mbc = Mbc::try_parse_bytes_mbc7(&numbered_rom(4).into()).unwrap();
+        mbc.set_tilt(Tilt { x: 1.0, y: -1.0 });
+
+        // registers need both enables
+        mbc.write(0x0000, 0x0A);
+        assert_eq!(mbc.read(0xA020), 0xFF);
+        mbc.write(0x4000, 0x40);
+        assert_eq!(mbc.read(0xA030), 0x80);
+
+        // latching only works after erasing
+        mbc.write(0xA010, 0xAA);
+        assert_eq!(mbc.read(0xA020), 0x00);
+        mbc.write(0xA000, 0x55);
+        mbc.write(0xA010, 0xAA);
+        assert_eq!((mbc.read(0xA030), mbc.read(0xA020)), (0x82, 0x40));
+        assert_eq!((mbc.read(0xA050), mbc.read(0xA040)), (0x81, 0x60));
+        assert_eq!(mbc.ram().map(|ram| ram.len()), Some(256));
+    }
+
+    #[test]
+    fn pocket_camera_maps_registers_over_ram() {
+        use hardware::cartridge::camera::TestPattern;
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let mut rom = numbered_rom(4);
+        rom[RAM_SIZE_ADDRESS as usize] = 0x04; // 128KB
+        let mut mbc = Mbc::try_parse_bytes_pocket_camera(&rom.into()).unwrap();
+        mbc.connect_camera(Rc::new(RefCell::new(TestPattern)));
+
+        mbc.write(0x0000, 0x0A);
+        mbc.write(0xA100, 0x12);
+
+        mbc.write(0x4000, 0x10);
+        mbc.write(0xA002, 0x10);
+        mbc.write(0xA000, 0x01);
+        assert_eq!(mbc.read(0xA000), 0x01);
+        mbc.emulate(CYCLES_PER_SECOND);
+        assert_eq!(mbc.read(0xA000), 0x00);
+
+        // with every threshold at zero the whole frame is white
+        mbc.write(0x4000, 0x00);
+        assert_eq!(mbc.read(0xA100), 0x00);
+    }
+
+    #[test]
+    fn tama5_runs_commands_through_register_window() {
+        fn set(mbc: &mut Mbc, register: Word, value: Word) {
+            mbc.write(0xA001, register);
+            mbc.write(0xA000, value);
+        }
+
+        let mut mbc = Mbc::try_parse_bytes_tama5(&numbered_rom(32).into()).unwrap();
+
+        set(&mut mbc, 0x0, 0x3);
+        set(&mut mbc, 0x1, 0x1);
+        assert_eq!(mbc.read(0x4000), 0x13);
+
+        mbc.write(0xA001, 0x0A);
+        assert_eq!(mbc.read(0xA000), 0xF1);
+
+        // write 0x5A to storage at 0x12, then read it back
+        set(&mut mbc, 0x4, 0xA);
+        set(&mut mbc, 0x5, 0x5);
+        set(&mut mbc, 0x6, 0x01);
+        set(&mut mbc, 0x7, 0x2);
+        set(&mut mbc, 0x6, 0x03);
+        set(&mut mbc, 0x7, 0x2);
+        mbc.write(0xA001, 0x0C);
+        assert_eq!(mbc.read(0xA000), 0xFA);
+        mbc.write(0xA001, 0x0D);
+        assert_eq!(mbc.read(0xA000), 0xF5);
+        assert_eq!(mbc.ram().map(|ram| ram[0x12]), Some(0x5A));
+
+        // read the tens of seconds
+        mbc.emulate(CYCLES_PER_SECOND * 42);
+        set(&mut mbc, 0x6, 0x06);
+        set(&mut mbc, 0x7, 0x1);
+        mbc.write(0xA001, 0x0C);
+        assert_eq!(mbc.read(0xA000), 0xF4);
     }
 }
@@ -12,21 +12,49 @@ mod mbc;
 pub use self::mbc::Mbc;
 
 pub mod header;
-use self::header::CartridgeKind;
+use self::header::{CartridgeHeader, CartridgeKind, RomSize, Verification};
+
+pub mod licensee;
+
+pub mod infrared;
+use self::infrared::InfraredPort;
+
+pub mod rtc;
+use self::rtc::Rtc;
+
+pub mod eeprom;
+
+pub mod camera;
+use self::camera::ImagePort;
+
+pub mod huc3;
+
+pub mod speaker;
+use self::speaker::SpeakerPort;
+
+pub mod tama5;
+
+pub mod patch;
+
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use failure::Error;
+use hardware::memory::addresses::memory_map::{ROM0_OFFSET, SROM_END};
 use hardware::memory::Memory;
-use hardware::memory::Memory32Kb;
-use hardware::Timer;
 use isa::{Address, Word};
+use system::Tilt;
 
 /// A gameboy cartridge
 #[derive(Debug, Clone)]
 pub struct Cartridge {
-    rom0: Memory32Kb,
+    header: CartridgeHeader,
+    verification: Verification,
+    /// The whole rom image, shared with the memory bank controller
+    rom: Rc<[Word]>,
     mbc: Option<Mbc>,
-    has_battery: bool,
-    timer: Option<Timer>,
+    /// Set when battery backed ram is written, until it's saved
+    dirty: bool,
 }
 
 impl Cartridge {
@@ -34,78 +62,234 @@ impl Cartridge {
     pub fn try_parse_bytes(bytes: &[u8]) -> Result<Self, Error> {
         use self::CartridgeKind::*;
         // don't try to parse if a header can't even be read
-        Self::validate_header(bytes)?;
-
-        let rom0 = Self::try_parse_rom0(bytes)?;
+        let header = CartridgeHeader::try_parse_bytes(bytes)?;
+        let verification = Verification::try_from_bytes(bytes)?;
+        if !verification.is_valid() {
+            warn!("Cartridge failed verification: {:?}", verification);
+        }
 
-        // get the cartridge type flag
-        let catridge_type = rom0.read(header::CATRIDGE_TYPE_ADDRESS).into();
+        let rom = Self::try_parse_rom(bytes, header.rom_size)?;
 
-        let mbc = match catridge_type {
+        let mbc = match header.kind {
             RomOnly => None,
-            MBC1 | MBC1Ram | MBC1RamBattery => Some(Mbc::try_parse_bytes_mbc1(bytes)?),
-            MBC2 | MBC2Battery => Some(Mbc::try_parse_bytes_mbc2(bytes)?),
+            MBC1 | MBC1Ram | MBC1RamBattery => Some(Mbc::try_parse_bytes_mbc1(&rom)?),
+            MBC2 | MBC2Battery => Some(Mbc::try_parse_bytes_mbc2(&rom)?),
             MBC3 | MBC3Ram | MBC3RamBattery | MBC3TimerBattery | MBC3TimerRamBattery => {
-                Some(Mbc::try_parse_bytes_mbc3(bytes)?)
+                Some(Mbc::try_parse_bytes_mbc3(&rom)?)
             }
             MBC5 | MBC5Ram | MBC5RamBattery | MBC5Rumble | MBC5RumbleRam | MBC5RumbleRamBattery => {
-                Some(Mbc::try_parse_bytes_mbc5(bytes)?)
+                Some(Mbc::try_parse_bytes_mbc5(&rom)?)
             }
-            HuC1RamBattery => Some(Mbc::try_parse_bytes_huc1(bytes)?),
-            _ => unimplemented!(),
+            HuC1RamBattery => Some(Mbc::try_parse_bytes_huc1(&rom)?),
+            MMM01 | MMM01Ram | MMM01RamBattery => Some(Mbc::try_parse_bytes_mmm01(&rom)?),
+            HuC3 => Some(Mbc::try_parse_bytes_huc3(&rom)?),
+            MBC7SensorRumbleRamBattery => Some(Mbc::try_parse_bytes_mbc7(&rom)?),
+            PocketCamera => Some(Mbc::try_parse_bytes_pocket_camera(&rom)?),
+            BandaiTama5 => Some(Mbc::try_parse_bytes_tama5(&rom)?),
+            kind => return Err(ParsingError::UnsupportedCartridgeKind { kind }.into()),
         };
 
-        let has_battery = catridge_type.has_battery();
+        Ok(Cartridge {
+            header,
+            verification,
+            rom,
+            mbc,
+            dirty: false,
+        })
+    }
+
+    /// Return the decoded header of the cartridge
+    pub fn header(&self) -> &CartridgeHeader {
+        &self.header
+    }
+
+    /// Return the results of checking the cartridge's checksums and logo when it was parsed
+    pub fn verification(&self) -> Verification {
+        self.verification
+    }
+
+    /// Emulate the hardware on the cartridge over a given number of cycles
+    pub fn emulate(&mut self, cycles: usize) {
+        if let Some(ref mut mbc) = self.mbc {
+            mbc.emulate(cycles)
+        }
+    }
+
+    /// Connect an infrared transceiver to the cartridge, if it has an infrared port
+    pub fn connect_infrared(&mut self, port: InfraredPort) {
+        if let Some(ref mut mbc) = self.mbc {
+            mbc.connect_infrared(port)
+        }
+    }
+
+    /// Connect a tone generator to the cartridge, if it has a speaker
+    pub fn connect_speaker(&mut self, port: SpeakerPort) {
+        if let Some(ref mut mbc) = self.mbc {
+            mbc.connect_speaker(port)
+        }
+    }
+
+    /// Connect an image provider to the cartridge, if it has a camera
+    pub fn connect_camera(&mut self, port: ImagePort) {
+        if let Some(ref mut mbc) = self.mbc {
+            mbc.connect_camera(port)
+        }
+    }
 
-        // make a timer if its required
-        let timer = if catridge_type.has_timer() {
-            Some(Timer::default())
+    /// Set the tilt the cartridge's accelerometer reads from, if it has one
+    pub fn set_tilt(&mut self, tilt: Tilt) {
+        if let Some(ref mut mbc) = self.mbc {
+            mbc.set_tilt(tilt)
+        }
+    }
+
+    /// Returns true if the cartridge's rumble motor is running
+    pub fn is_rumbling(&self) -> bool {
+        self.mbc.as_ref().map_or(false, Mbc::is_rumbling)
+    }
+
+    /// Return the ram a battery keeps alive while the cartridge is unplugged, if it has any
+    pub fn battery_ram(&self) -> Option<&[Word]> {
+        if self.header.kind.has_battery() {
+            self.mbc.as_ref().and_then(Mbc::ram)
         } else {
             None
-        };
+        }
+    }
 
-        Ok(Cartridge {
-            rom0,
-            mbc,
-            has_battery,
-            timer,
-        })
+    /// Export the battery backed ram in the raw `.sav` layout, followed by the clock if the
+    /// cartridge keeps one outside of ram. Returns `None` if there is nothing to save
+    pub fn export_save(&self) -> Option<Vec<u8>> {
+        let mut save = self.battery_ram().map_or_else(Vec::new, <[Word]>::to_vec);
+        if self.header.kind.has_battery() {
+            if let Some(clock) = self.save_clock() {
+                save.extend(clock);
+            }
+        }
+
+        if save.is_empty() {
+            None
+        } else {
+            Some(save)
+        }
+    }
+
+    /// Import a save exported by `export_save`, or a raw `.sav` from another emulator. Bytes
+    /// past the end of ram are loaded as the clock, if the cartridge has one
+    pub fn import_save(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.import_save_at(bytes, None)
+    }
+
+    /// Import a save like `import_save`, then advance an MBC3 clock by the wall-clock time that
+    /// has passed since the save was written
+    pub fn import_save_catching_up(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.import_save_at(bytes, Some(unix_time()))
     }
 
-    /// Returns an `[failure::Error]` if there is a problem parsing the header
-    fn validate_header(bytes: &[u8]) -> Result<(), Error> {
-        // basic check. make sure there are enough bytes to form a header
-        if bytes.len() < 0x14 {
-            return Err(ParsingError::InvalidHeaderLength {
+    fn import_save_at(&mut self, bytes: &[u8], now: Option<u64>) -> Result<(), Error> {
+        if !self.header.kind.has_battery() {
+            return Err(SaveError::NoBattery.into());
+        }
+
+        let length = self.battery_ram().map_or(0, <[Word]>::len);
+        if bytes.len() < length {
+            return Err(SaveError::InvalidSaveLength {
                 length: bytes.len(),
+                expected: length,
             }.into());
         }
 
+        if let Some(ram) = self.mbc.as_mut().and_then(Mbc::ram_mut) {
+            ram.copy_from_slice(&bytes[..length]);
+        }
+        // raw saves from other emulators often leave the clock out
+        if bytes.len() > length && !self.load_clock_at(&bytes[length..], now) {
+            warn!("Ignoring {} bytes past the end of the save", bytes.len() - length);
+        }
+
+        self.dirty = false;
         Ok(())
     }
 
-    fn try_parse_rom0(bytes: &[u8]) -> Result<Memory32Kb, Error> {
-        // FIXME: bad alloc, maybe use cow?
-        let mut inner = [0; 1024 * 32]; // 32kb
-        inner.copy_from_slice(&bytes[0..1024 * 32]);
-        Ok(Memory32Kb::from(inner))
+    /// Returns true if battery backed ram has been written since it was last saved
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Mark battery backed ram as saved
+    pub fn mark_saved(&mut self) {
+        self.dirty = false;
+    }
+
+    /// Serialize the clock the cartridge battery keeps running, to be saved next to its ram.
+    /// MBC3 clocks are stored as the 48-byte footer other emulators use, stamped with the
+    /// current time
+    pub fn save_clock(&self) -> Option<Vec<u8>> {
+        let mbc = self.mbc.as_ref()?;
+        match mbc.rtc() {
+            Some(rtc) => Some(rtc.to_footer(unix_time())),
+            None => mbc.save_clock(),
+        }
+    }
+
+    /// Restore a clock serialized by `save_clock`, or an MBC3 clock from either footer other
+    /// emulators use. Returns false if the cartridge has no such clock or the bytes don't hold
+    /// one
+    pub fn load_clock(&mut self, bytes: &[u8]) -> bool {
+        self.load_clock_at(bytes, None)
+    }
+
+    /// Restore a clock, advancing an MBC3 clock to `now` if it's given
+    fn load_clock_at(&mut self, bytes: &[u8], now: Option<u64>) -> bool {
+        let mbc = match self.mbc {
+            Some(ref mut mbc) => mbc,
+            None => return false,
+        };
+
+        match (mbc.rtc_mut(), Rtc::from_footer(bytes)) {
+            (Some(rtc), Some((saved, timestamp))) => {
+                *rtc = saved;
+                if let Some(now) = now {
+                    rtc.advance(now.saturating_sub(timestamp));
+                }
+                true
+            }
+            (Some(_), None) => false,
+            (None, _) => mbc.load_clock(bytes),
+        }
+    }
+
+    /// Validate the length of a rom image against the size in its header
+    fn try_parse_rom(bytes: &[u8], size: RomSize) -> Result<Rc<[Word]>, Error> {
+        if bytes.len() < size.bytes() {
+            return Err(ParsingError::InvalidRomLength {
+                length: bytes.len(),
+                expected: size.bytes(),
+            }.into());
+        }
+
+        // multicarts only describe their first game in the header, so anything past the
+        // header size is kept
+        Ok(Rc::from(bytes))
     }
 }
 
 impl Memory for Cartridge {
     fn read(&self, address: Address) -> Word {
-        // TODO: need to check the address to know whether to use mbc or rom0
-        if let Some(ref mbc) = self.mbc {
-            mbc.read(address)
-        } else {
-            Word::default()
+        match self.mbc {
+            Some(ref mbc) => mbc.read(address),
+            // without a controller the first 32KB is mapped directly, with nothing in external ram
+            None => match address {
+                ROM0_OFFSET...SROM_END => self.rom[usize::from(address)],
+                _ => 0xFF,
+            },
         }
     }
 
     fn write(&mut self, address: Address, value: Word) {
-        // TODO: need to check the address to know whether to use mbc or rom0
         if let Some(ref mut mbc) = self.mbc {
-            mbc.write(address, value)
+            let changed = mbc.write_tracked(address, value);
+            self.dirty |= changed && self.header.kind.has_battery();
         }
     }
 }
@@ -115,4 +299,149 @@ impl Memory for Cartridge {
 pub enum ParsingError {
     #[fail(display = "Header invalid length {}", length)]
     InvalidHeaderLength { length: usize },
+    #[fail(display = "Rom invalid length {}, expected at least {}", length, expected)]
+    InvalidRomLength { length: usize, expected: usize },
+    #[fail(display = "Cartridge type invalid byte {:#04X}", value)]
+    InvalidCartridgeKind { value: Word },
+    #[fail(display = "Rom size invalid byte {:#04X}", value)]
+    InvalidRomSize { value: Word },
+    #[fail(display = "Ram size invalid byte {:#04X}", value)]
+    InvalidRamSize { value: Word },
+    #[fail(display = "Destination code invalid byte {:#04X}", value)]
+    InvalidDestination { value: Word },
+    #[fail(display = "Cartridge type {:?} is not supported", kind)]
+    UnsupportedCartridgeKind { kind: CartridgeKind },
+    #[fail(display = "Rom size {:?} is too large for {}", size, mapper)]
+    RomTooLarge { size: RomSize, mapper: &'static str },
+}
+
+/// Return the current wall-clock time in seconds since the unix epoch
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+/// Errors that can occur importing a save
+#[derive(Fail, Debug, Clone)]
+pub enum SaveError {
+    #[fail(display = "Cartridge has no battery to save")]
+    NoBattery,
+    #[fail(display = "Save invalid length {}, expected at least {}", length, expected)]
+    InvalidSaveLength { length: usize, expected: usize },
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rom_only_maps_first_32kb() {
+        let mut bytes = vec![0; 0x8000];
+        bytes[0x0000] = 0x12;
+        bytes[0x7FFF] = 0x34;
+        let cartridge = Cartridge::try_parse_bytes(&bytes).unwrap();
+        assert_eq!(cartridge.read(0x0000), 0x12);
+        assert_eq!(cartridge.read(0x7FFF), 0x34);
+        assert_eq!(cartridge.read(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn rejects_roms_shorter_than_header_size() {
+        let mut bytes = vec![0; 0x8000];
+        bytes[header::ROM_SIZE_ADDRESS as usize] = 0x01; // 64KB
+        assert!(Cartridge::try_parse_bytes(&bytes).is_err());
+        assert!(Cartridge::try_parse_bytes(&bytes[..0x4000]).is_err());
+    }
+
+    #[test]
+    fn rejects_unsupported_kinds() {
+        let mut bytes = vec![0; 0x8000];
+        bytes[header::CATRIDGE_TYPE_ADDRESS as usize] = 0x20; // MBC6
+        let error = Cartridge::try_parse_bytes(&bytes).unwrap_err();
+        match error.downcast::<ParsingError>() {
+            Ok(ParsingError::UnsupportedCartridgeKind { kind }) => {
+                assert_eq!(kind, CartridgeKind::MBC6)
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_mbc7_cartridges() {
+        let mut bytes = vec![0; 0x10000];
+        bytes[header::CATRIDGE_TYPE_ADDRESS as usize] = 0x22; // MBC7, sensor, rumble and battery
+        bytes[header::ROM_SIZE_ADDRESS as usize] = 0x01; // 64KB
+        let cartridge = Cartridge::try_parse_bytes(&bytes).unwrap();
+        assert_eq!(cartridge.header().kind, CartridgeKind::MBC7SensorRumbleRamBattery);
+        assert_eq!(cartridge.battery_ram().map(<[Word]>::len), Some(0x100));
+    }
+
+    #[test]
+    fn save_round_trips_and_tracks_writes() {
+        let mut bytes = vec![0; 0x8000];
+        bytes[header::CATRIDGE_TYPE_ADDRESS as usize] = 0x03; // MBC1, ram and battery
+        bytes[header::RAM_SIZE_ADDRESS as usize] = 0x02; // 8KB
+        let mut cartridge = Cartridge::try_parse_bytes(&bytes).unwrap();
+
+        cartridge.write(0x0000, 0x0A);
+        cartridge.write(0xA123, 0x42);
+        assert!(cartridge.is_dirty());
+
+        let save = cartridge.export_save().unwrap();
+        assert_eq!((save.len(), save[0x123]), (0x2000, 0x42));
+
+        let mut fresh = Cartridge::try_parse_bytes(&bytes).unwrap();
+        assert!(fresh.import_save(&save[..0x1000]).is_err());
+        fresh.import_save(&save).unwrap();
+        fresh.write(0x0000, 0x0A);
+        assert_eq!(fresh.read(0xA123), 0x42);
+        assert!(!fresh.is_dirty());
+    }
+
+    #[test]
+    fn only_changed_ram_makes_saves_dirty() {
+        let mut bytes = vec![0; 0x8000];
+        bytes[header::CATRIDGE_TYPE_ADDRESS as usize] = 0x03; // MBC1, ram and battery
+        bytes[header::RAM_SIZE_ADDRESS as usize] = 0x02; // 8KB
+        let mut cartridge = Cartridge::try_parse_bytes(&bytes).unwrap();
+
+        // ram is disabled until enabled
+        cartridge.write(0xA123, 0x42);
+        assert!(!cartridge.is_dirty());
+        assert_ne!(cartridge.read(0xA123), 0x42);
+
+        cartridge.write(0x0000, 0x0A);
+        cartridge.write(0x2000, 0x02);
+        cartridge.write(0xA123, 0x00);
+        assert!(!cartridge.is_dirty());
+
+        cartridge.write(0xA123, 0x42);
+        assert!(cartridge.is_dirty());
+    }
+
+    #[test]
+    fn mbc3_saves_carry_clock_footer() {
+        let mut bytes = vec![0; 0x8000];
+        bytes[header::CATRIDGE_TYPE_ADDRESS as usize] = 0x10; // MBC3, timer, ram and battery
+        bytes[header::RAM_SIZE_ADDRESS as usize] = 0x02; // 8KB
+        let cartridge = Cartridge::try_parse_bytes(&bytes).unwrap();
+
+        let save = cartridge.export_save().unwrap();
+        assert_eq!(save.len(), 0x2000 + rtc::FOOTER_SIZE);
+
+        // a save written a day ago, in the short footer layout
+        let mut old = save[..0x2000 + rtc::SHORT_FOOTER_SIZE].to_vec();
+        let timestamp = unix_time() - 24 * 60 * 60;
+        for i in 0..4 {
+            old[0x2000 + 40 + i] = (timestamp >> (i * 8)) as u8;
+        }
+
+        let mut fresh = Cartridge::try_parse_bytes(&bytes).unwrap();
+        fresh.import_save(&old).unwrap();
+        assert_eq!(fresh.mbc.as_ref().and_then(Mbc::rtc).unwrap().registers().days, 0);
+        fresh.import_save_catching_up(&old).unwrap();
+        assert_eq!(fresh.mbc.as_ref().and_then(Mbc::rtc).unwrap().registers().days, 1);
+    }
 }
@@ -0,0 +1,533 @@
+// Copyright 2018 Will Johnston
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! IPS, UPS and BPS rom patches
+//!
+//! Patches are applied to a rom image before it's parsed into a cartridge. UPS and BPS
+//! patches carry CRC32 checksums of the source, the result and the patch itself, which are
+//! all checked.
+
+use failure::Error;
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_EOF: &[u8] = b"EOF";
+const UPS_MAGIC: &[u8] = b"UPS1";
+const BPS_MAGIC: &[u8] = b"BPS1";
+
+/// Size of the checksums at the end of UPS and BPS patches
+const FOOTER_SIZE: usize = 12;
+
+/// The largest rom a UPS or BPS patch may make, the size of the largest MBC5 rom
+const MAX_TARGET_SIZE: usize = 8 * 1024 * 1024;
+
+const BPS_SOURCE_READ: u64 = 0;
+const BPS_TARGET_READ: u64 = 1;
+const BPS_SOURCE_COPY: u64 = 2;
+const BPS_TARGET_COPY: u64 = 3;
+
+/// Apply a patch to a rom, choosing the format from the patch header
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, Error> {
+    if patch.starts_with(IPS_MAGIC) {
+        apply_ips(rom, patch)
+    } else if patch.starts_with(UPS_MAGIC) {
+        apply_ups(rom, patch)
+    } else if patch.starts_with(BPS_MAGIC) {
+        apply_bps(rom, patch)
+    } else {
+        Err(PatchError::UnknownFormat.into())
+    }
+}
+
+/// Apply an IPS patch to a rom
+pub fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut reader = Reader::new(patch);
+    reader.expect(IPS_MAGIC)?;
+
+    let mut target = rom.to_vec();
+    loop {
+        if reader.remaining().starts_with(IPS_EOF) {
+            reader.take(IPS_EOF.len())?;
+            break;
+        }
+
+        let offset = reader.big_endian(3)? as usize;
+        let (length, value) = match reader.big_endian(2)? as usize {
+            // run length encoded records repeat a single byte
+            0 => (reader.big_endian(2)? as usize, Some(reader.byte()?)),
+            length => (length, None),
+        };
+
+        if offset + length > MAX_TARGET_SIZE {
+            return Err(PatchError::TargetTooLarge {
+                size: (offset + length) as u64,
+            }.into());
+        }
+        if target.len() < offset + length {
+            target.resize(offset + length, 0);
+        }
+        match value {
+            Some(value) => target[offset..offset + length]
+                .iter_mut()
+                .for_each(|byte| *byte = value),
+            None => target[offset..offset + length].copy_from_slice(reader.take(length)?),
+        }
+    }
+
+    // an optional extension truncates the result
+    if reader.remaining().len() == 3 {
+        let length = reader.big_endian(3)? as usize;
+        target.truncate(length);
+    }
+    Ok(target)
+}
+
+/// Apply a UPS patch to a rom, checking its checksums
+pub fn apply_ups(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, Error> {
+    let (body, checksums) = split_footer(patch)?;
+    let mut reader = Reader::new(body);
+    reader.expect(UPS_MAGIC)?;
+    check(Checksum::Source, checksums[0], crc32(rom))?;
+
+    let source_size = reader.varint()? as usize;
+    let target_size = target_size(&mut reader)?;
+    if rom.len() != source_size {
+        return Err(PatchError::SourceSize {
+            expected: source_size,
+            actual: rom.len(),
+        }.into());
+    }
+
+    let mut target = rom.to_vec();
+    target.resize(target_size, 0);
+    let mut offset: usize = 0;
+    while !reader.remaining().is_empty() {
+        offset = offset
+            .checked_add(reader.offset()?)
+            .ok_or(PatchError::Truncated)?;
+        // each hunk is xored into the rom, up to a terminating zero
+        loop {
+            let value = reader.byte()?;
+            if let Some(byte) = target.get_mut(offset) {
+                *byte ^= value;
+            }
+            offset = offset.checked_add(1).ok_or(PatchError::Truncated)?;
+            if value == 0 {
+                break;
+            }
+        }
+    }
+
+    check(Checksum::Target, checksums[1], crc32(&target))?;
+    Ok(target)
+}
+
+/// Apply a BPS patch to a rom, checking its checksums
+pub fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, Error> {
+    let (body, checksums) = split_footer(patch)?;
+    let mut reader = Reader::new(body);
+    reader.expect(BPS_MAGIC)?;
+    check(Checksum::Source, checksums[0], crc32(rom))?;
+
+    let source_size = reader.varint()? as usize;
+    let target_size = target_size(&mut reader)?;
+    let metadata_size = reader.varint()? as usize;
+    reader.take(metadata_size)?;
+    if rom.len() != source_size {
+        return Err(PatchError::SourceSize {
+            expected: source_size,
+            actual: rom.len(),
+        }.into());
+    }
+
+    let mut target = Vec::with_capacity(target_size);
+    let mut source_offset: i64 = 0;
+    let mut target_offset: i64 = 0;
+    while !reader.remaining().is_empty() {
+        let action = reader.varint()?;
+        let length = (action >> 2) as usize + 1;
+        if length > target_size - target.len() {
+            return Err(PatchError::Truncated.into());
+        }
+        match action & 0x03 {
+            BPS_SOURCE_READ => {
+                let bytes = slice(rom, target.len() as i64, length)?;
+                target.extend_from_slice(bytes);
+            }
+            BPS_TARGET_READ => target.extend_from_slice(reader.take(length)?),
+            BPS_SOURCE_COPY => {
+                source_offset = advance(source_offset, reader.signed_offset()?)?;
+                let bytes = slice(rom, source_offset, length)?;
+                target.extend_from_slice(bytes);
+                source_offset = advance(source_offset, length as i64)?;
+            }
+            BPS_TARGET_COPY => {
+                target_offset = advance(target_offset, reader.signed_offset()?)?;
+                // copies may overlap what they write, so they go a byte at a time
+                for _ in 0..length {
+                    let byte = *target
+                        .get(target_offset as usize)
+                        .ok_or(PatchError::Truncated)?;
+                    target.push(byte);
+                    target_offset = advance(target_offset, 1)?;
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    if target.len() != target_size {
+        return Err(PatchError::Truncated.into());
+    }
+    check(Checksum::Target, checksums[1], crc32(&target))?;
+    Ok(target)
+}
+
+/// Read the size of the rom a UPS or BPS patch makes, refusing sizes no cartridge could have
+fn target_size(reader: &mut Reader) -> Result<usize, PatchError> {
+    match reader.varint()? {
+        size if size > MAX_TARGET_SIZE as u64 => Err(PatchError::TargetTooLarge { size }),
+        size => Ok(size as usize),
+    }
+}
+
+/// Move a BPS offset by a relative amount, failing rather than overflowing
+fn advance(offset: i64, delta: i64) -> Result<i64, PatchError> {
+    offset.checked_add(delta).ok_or(PatchError::Truncated)
+}
+
+/// Return `length` bytes of a rom from an offset a patch gave, which may be out of range
+fn slice(rom: &[u8], offset: i64, length: usize) -> Result<&[u8], PatchError> {
+    if offset < 0 {
+        return Err(PatchError::Truncated);
+    }
+    let start = offset as usize;
+    start
+        .checked_add(length)
+        .and_then(|end| rom.get(start..end))
+        .ok_or(PatchError::Truncated)
+}
+
+/// Split the checksums from the end of a UPS or BPS patch, checking the patch's own checksum
+fn split_footer(patch: &[u8]) -> Result<(&[u8], [u32; 3]), Error> {
+    if patch.len() < FOOTER_SIZE {
+        return Err(PatchError::Truncated.into());
+    }
+
+    let body = &patch[..patch.len() - FOOTER_SIZE];
+    let mut footer = Reader::new(&patch[body.len()..]);
+    let checksums = [
+        footer.little_endian(4)? as u32,
+        footer.little_endian(4)? as u32,
+        footer.little_endian(4)? as u32,
+    ];
+    check(Checksum::Patch, checksums[2], crc32(&patch[..patch.len() - 4]))?;
+    Ok((body, checksums))
+}
+
+fn check(checksum: Checksum, expected: u32, actual: u32) -> Result<(), PatchError> {
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(PatchError::ChecksumMismatch {
+            checksum,
+            expected,
+            actual,
+        })
+    }
+}
+
+/// Compute the CRC32 of some bytes, as used by zip and png
+pub fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, &byte| {
+        (0..8).fold(crc ^ u32::from(byte), |crc, _| {
+            (crc >> 1) ^ (0xEDB8_8320 & (!(crc & 1)).wrapping_add(1))
+        })
+    })
+}
+
+/// A cursor over the bytes of a patch
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes }
+    }
+
+    fn remaining(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], PatchError> {
+        if self.bytes.len() < length {
+            return Err(PatchError::Truncated);
+        }
+        let (taken, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8, PatchError> {
+        self.take(1).map(|bytes| bytes[0])
+    }
+
+    fn expect(&mut self, magic: &[u8]) -> Result<(), PatchError> {
+        if self.take(magic.len())? == magic {
+            Ok(())
+        } else {
+            Err(PatchError::UnknownFormat)
+        }
+    }
+
+    fn big_endian(&mut self, length: usize) -> Result<u64, PatchError> {
+        let bytes = self.take(length)?;
+        Ok(bytes
+            .iter()
+            .fold(0, |value, &byte| value << 8 | u64::from(byte)))
+    }
+
+    fn little_endian(&mut self, length: usize) -> Result<u64, PatchError> {
+        let bytes = self.take(length)?;
+        Ok(bytes
+            .iter()
+            .rev()
+            .fold(0, |value, &byte| value << 8 | u64::from(byte)))
+    }
+
+    /// Read a UPS and BPS variable length number. Each byte holds 7 bits, with the top bit set
+    /// on the last byte
+    fn varint(&mut self) -> Result<u64, PatchError> {
+        let mut value = 0u64;
+        let mut shift = 1u64;
+        loop {
+            let byte = self.byte()?;
+            value = value.wrapping_add(u64::from(byte & 0x7F).wrapping_mul(shift));
+            if byte & 0x80 != 0 {
+                return Ok(value);
+            }
+            shift = shift.wrapping_shl(7);
+            value = value.wrapping_add(shift);
+        }
+    }
+
+    /// Read a UPS relative offset, which can't reach past the largest target
+    fn offset(&mut self) -> Result<usize, PatchError> {
+        match self.varint()? {
+            offset if offset > MAX_TARGET_SIZE as u64 => Err(PatchError::Truncated),
+            offset => Ok(offset as usize),
+        }
+    }
+
+    /// Read a BPS relative offset, a varint with the sign in the lowest bit, which can't reach
+    /// past the largest target
+    fn signed_offset(&mut self) -> Result<i64, PatchError> {
+        let value = self.varint()?;
+        let magnitude = (value >> 1) as i64;
+        if value >> 1 > MAX_TARGET_SIZE as u64 {
+            return Err(PatchError::Truncated);
+        }
+        Ok(if value & 1 != 0 { -magnitude } else { magnitude })
+    }
+}
+
+/// Which checksum of a patch failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checksum {
+    /// The rom the patch was made for
+    Source,
+    /// The patched rom
+    Target,
+    /// The patch itself
+    Patch,
+}
+
+/// Errors that can occur applying a patch
+#[derive(Fail, Debug, Clone)]
+pub enum PatchError {
+    #[fail(display = "Patch is not an IPS, UPS or BPS patch")]
+    UnknownFormat,
+    #[fail(display = "Patch ended unexpectedly or points outside the rom")]
+    Truncated,
+    #[fail(display = "Patch made for a rom of {} bytes, not {}", expected, actual)]
+    SourceSize { expected: usize, actual: usize },
+    #[fail(display = "Patch makes a rom of {} bytes, larger than 8MB", size)]
+    TargetTooLarge { size: u64 },
+    #[fail(
+        display = "{:?} checksum mismatch, expected {:08X} but found {:08X}",
+        checksum,
+        expected,
+        actual
+    )]
+    ChecksumMismatch {
+        checksum: Checksum,
+        expected: u32,
+        actual: u32,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn varint(mut value: u64) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        loop {
+            let low = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                bytes.push(0x80 | low);
+                return bytes;
+            }
+            bytes.push(low);
+            value -= 1;
+        }
+    }
+
+    /// Append the source, target and patch checksums to a UPS or BPS patch
+    fn finish(mut patch: Vec<u8>, source: &[u8], target: &[u8]) -> Vec<u8> {
+        fn push(patch: &mut Vec<u8>, crc: u32) {
+            patch.extend((0..4).map(|i| (crc >> (i * 8)) as u8));
+        }
+        push(&mut patch, crc32(source));
+        push(&mut patch, crc32(target));
+        let crc = crc32(&patch);
+        push(&mut patch, crc);
+        patch
+    }
+
+    #[test]
+    fn crc32_matches_reference() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn applies_ips_records() {
+        let mut patch = b"PATCH".to_vec();
+        patch.extend_from_slice(&[0x00, 0x00, 0x01, 0x00, 0x02, 0xAA, 0xBB]);
+        patch.extend_from_slice(&[0x00, 0x00, 0x06, 0x00, 0x00, 0x00, 0x03, 0xCC]);
+        patch.extend_from_slice(b"EOF");
+
+        let target = apply(&[0; 4], &patch).unwrap();
+        assert_eq!(target, vec![0x00, 0xAA, 0xBB, 0x00, 0x00, 0x00, 0xCC, 0xCC, 0xCC]);
+    }
+
+    #[test]
+    fn applies_ups_and_checks_crcs() {
+        let source = b"HELLO WORLD".to_vec();
+        let target = b"HELLO THERE!".to_vec();
+
+        let mut patch = b"UPS1".to_vec();
+        patch.extend(varint(source.len() as u64));
+        patch.extend(varint(target.len() as u64));
+        patch.extend(varint(6));
+        for (a, b) in source[6..].iter().chain(&[0]).zip(&target[6..]) {
+            patch.push(a ^ b);
+        }
+        patch.push(0);
+        let patch = finish(patch, &source, &target);
+
+        assert_eq!(apply(&source, &patch).unwrap(), target);
+        assert!(apply(b"HELLO WORLF", &patch).is_err());
+    }
+
+    #[test]
+    fn applies_bps_actions() {
+        let source = b"ABCDEFGH".to_vec();
+        let target = b"ABCDxyzEFGHGHGHGH".to_vec();
+
+        let mut patch = b"BPS1".to_vec();
+        patch.extend(varint(source.len() as u64));
+        patch.extend(varint(target.len() as u64));
+        patch.extend(varint(0));
+        patch.extend(varint((4 - 1) << 2 | BPS_SOURCE_READ));
+        patch.extend(varint((3 - 1) << 2 | BPS_TARGET_READ));
+        patch.extend_from_slice(b"xyz");
+        patch.extend(varint((4 - 1) << 2 | BPS_SOURCE_COPY));
+        patch.extend(varint(4 << 1));
+        // overlapping copies repeat what they just wrote
+        patch.extend(varint((6 - 1) << 2 | BPS_TARGET_COPY));
+        patch.extend(varint(9 << 1));
+        let patch = finish(patch, &source, &target);
+
+        assert_eq!(apply(&source, &patch).unwrap(), target);
+    }
+
+    #[test]
+    fn rejects_bps_copies_outside_the_rom() {
+        let source = b"ABCDEFGH".to_vec();
+        let mut patch = b"BPS1".to_vec();
+        patch.extend(varint(source.len() as u64));
+        patch.extend(varint(4));
+        patch.extend(varint(0));
+        patch.extend(varint((4 - 1) << 2 | BPS_SOURCE_COPY));
+        patch.extend(varint(8 << 1 | 1));
+        let patch = finish(patch, &source, b"ABCD");
+
+        assert!(apply(&source, &patch).is_err());
+    }
+
+    #[test]
+    fn rejects_oversized_targets() {
+        let source = b"ABCDEFGH".to_vec();
+        for magic in &[UPS_MAGIC, BPS_MAGIC] {
+            let mut patch = magic.to_vec();
+            patch.extend(varint(source.len() as u64));
+            patch.extend(varint(u64::MAX >> 1));
+            patch.extend(varint(0));
+            let patch = finish(patch, &source, &[]);
+
+            let error = apply(&source, &patch).unwrap_err().downcast::<PatchError>().unwrap();
+            match error {
+                PatchError::TargetTooLarge { .. } => {}
+                error => panic!("unexpected error {}", error),
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_overflowing_offsets() {
+        let source = b"ABCDEFGH".to_vec();
+        let mut ups = b"UPS1".to_vec();
+        ups.extend(varint(source.len() as u64));
+        ups.extend(varint(source.len() as u64));
+        ups.extend(varint(u64::MAX));
+        ups.push(0);
+
+        let mut bps = b"BPS1".to_vec();
+        bps.extend(varint(source.len() as u64));
+        bps.extend(varint(source.len() as u64));
+        bps.extend(varint(0));
+        bps.extend(varint(BPS_TARGET_READ));
+        bps.push(b'A');
+        bps.extend(varint(BPS_TARGET_COPY));
+        bps.extend(varint(u64::MAX - 1));
+
+        for patch in [ups, bps] {
+            let patch = finish(patch, &source, &source);
+            let error = apply(&source, &patch).unwrap_err().downcast::<PatchError>().unwrap();
+            match error {
+                PatchError::Truncated => {}
+                error => panic!("unexpected error {}", error),
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_ips_records() {
+        let mut patch = b"PATCH".to_vec();
+        patch.extend_from_slice(&[0xFF, 0xFF, 0xFF, 0x00, 0x00, 0xFF, 0xFF, 0xCC]);
+        patch.extend_from_slice(b"EOF");
+
+        let error = apply(&[0; 4], &patch).unwrap_err().downcast::<PatchError>().unwrap();
+        match error {
+            PatchError::TargetTooLarge { .. } => {}
+            error => panic!("unexpected error {}", error),
+        }
+    }
+}
@@ -0,0 +1,323 @@
+// Copyright 2018 Will Johnston
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! MBC3 real time clock
+
+use hardware::cpu::CYCLES_PER_SECOND;
+use isa::Word;
+
+/// Seconds register select value
+pub const SECONDS: Word = 0x08;
+
+/// Minutes register select value
+pub const MINUTES: Word = 0x09;
+
+/// Hours register select value
+pub const HOURS: Word = 0x0A;
+
+/// Lower 8 bits of the day counter register select value
+pub const DAYS_LOW: Word = 0x0B;
+
+/// Upper day counter bit, halt and day carry register select value
+pub const DAYS_HIGH: Word = 0x0C;
+
+/// Size of the footer VBA and BGB append to saves, with a 64-bit timestamp
+pub const FOOTER_SIZE: usize = 48;
+
+/// Size of the older footer variant, with a 32-bit timestamp
+pub const SHORT_FOOTER_SIZE: usize = 44;
+
+/// Registers in the order they're stored in a footer
+const FOOTER_REGISTERS: [Word; 5] = [SECONDS, MINUTES, HOURS, DAYS_LOW, DAYS_HIGH];
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+const DAYS_HIGH_BIT: Word = 0x01;
+const HALT_BIT: Word = 0x40;
+const DAY_CARRY_BIT: Word = 0x80;
+
+/// A snapshot of the clock registers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RtcRegisters {
+    pub seconds: Word,
+    pub minutes: Word,
+    pub hours: Word,
+    /// 9-bit day counter
+    pub days: u16,
+    pub halted: bool,
+    /// Set when the day counter overflows, until it is cleared by the game
+    pub day_carry: bool,
+}
+
+impl RtcRegisters {
+    /// Advance the clock by one second. Registers written with out of range values count up
+    /// to the limit of their bits and wrap to zero without carrying
+    fn tick(&mut self) {
+        self.seconds = (self.seconds + 1) & 0x3F;
+        if self.seconds != 60 {
+            return;
+        }
+        self.seconds = 0;
+
+        self.minutes = (self.minutes + 1) & 0x3F;
+        if self.minutes != 60 {
+            return;
+        }
+        self.minutes = 0;
+
+        self.hours = (self.hours + 1) & 0x1F;
+        if self.hours != 24 {
+            return;
+        }
+        self.hours = 0;
+
+        self.days = (self.days + 1) & 0x1FF;
+        if self.days == 0 {
+            self.day_carry = true;
+        }
+    }
+
+    /// Returns true if every register is in the range the clock counts through
+    fn is_in_range(&self) -> bool {
+        self.seconds < 60 && self.minutes < 60 && self.hours < 24
+    }
+
+    /// Advance the clock by a number of seconds
+    fn advance(&mut self, mut seconds: u64) {
+        // registers written out of range wrap on their own, so they're ticked into range first
+        while seconds > 0 && !self.is_in_range() {
+            self.tick();
+            seconds -= 1;
+        }
+        if seconds == 0 {
+            return;
+        }
+
+        let total = u64::from(self.seconds)
+            + u64::from(self.minutes) * 60
+            + u64::from(self.hours) * 60 * 60
+            + u64::from(self.days) * SECONDS_PER_DAY
+            + seconds;
+        let days = total / SECONDS_PER_DAY;
+        self.seconds = (total % 60) as Word;
+        self.minutes = (total / 60 % 60) as Word;
+        self.hours = (total / (60 * 60) % 24) as Word;
+        self.days = (days % 0x200) as u16;
+        self.day_carry |= days >= 0x200;
+    }
+
+    /// Read a clock register by its select value
+    fn read(&self, register: Word) -> Word {
+        match register {
+            SECONDS => self.seconds,
+            MINUTES => self.minutes,
+            HOURS => self.hours,
+            DAYS_LOW => self.days as Word,
+            DAYS_HIGH => {
+                let mut value = (self.days >> 8) as Word & DAYS_HIGH_BIT;
+                if self.halted {
+                    value |= HALT_BIT;
+                }
+                if self.day_carry {
+                    value |= DAY_CARRY_BIT;
+                }
+                value
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Write a clock register by its select value
+    fn write(&mut self, register: Word, value: Word) {
+        match register {
+            SECONDS => self.seconds = value & 0x3F,
+            MINUTES => self.minutes = value & 0x3F,
+            HOURS => self.hours = value & 0x1F,
+            DAYS_LOW => self.days = (self.days & 0x100) | u16::from(value),
+            DAYS_HIGH => {
+                self.days = (self.days & 0xFF) | (u16::from(value & DAYS_HIGH_BIT) << 8);
+                self.halted = value & HALT_BIT != 0;
+                self.day_carry = value & DAY_CARRY_BIT != 0;
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// A real time clock, counting seconds from emulated cycles
+#[derive(Debug, Clone, Default)]
+pub struct Rtc {
+    live: RtcRegisters,
+    latched: RtcRegisters,
+    /// Cycles counted towards the next second
+    cycles: usize,
+}
+
+impl Rtc {
+    /// Emulate the clock over a given number of cycles
+    pub fn emulate(&mut self, cycles: usize) {
+        if self.live.halted {
+            return;
+        }
+
+        self.cycles += cycles;
+        while self.cycles >= CYCLES_PER_SECOND {
+            self.cycles -= CYCLES_PER_SECOND;
+            self.live.tick();
+        }
+    }
+
+    /// Copy the counting registers into the registers games read from
+    pub fn latch(&mut self) {
+        self.latched = self.live;
+    }
+
+    /// Read a latched clock register by its select value
+    pub fn read(&self, register: Word) -> Word {
+        self.latched.read(register)
+    }
+
+    /// Write a clock register by its select value
+    pub fn write(&mut self, register: Word, value: Word) {
+        // writing the seconds resets the divider counting towards the next second
+        if register == SECONDS {
+            self.cycles = 0;
+        }
+        self.live.write(register, value);
+    }
+
+    /// Return the counting clock registers
+    pub fn registers(&self) -> RtcRegisters {
+        self.live
+    }
+
+    /// Return the clock registers as they were last latched
+    pub fn latched_registers(&self) -> RtcRegisters {
+        self.latched
+    }
+
+    /// Advance the clock by a number of seconds, unless it's halted
+    pub fn advance(&mut self, seconds: u64) {
+        if !self.live.halted {
+            self.live.advance(seconds);
+        }
+    }
+
+    /// Serialize the clock as a 48-byte footer: the counting then the latched registers as
+    /// 32-bit little endian values, followed by `timestamp` as a 64-bit little endian value
+    pub fn to_footer(&self, timestamp: u64) -> Vec<u8> {
+        let mut footer = Vec::with_capacity(FOOTER_SIZE);
+        for registers in &[self.live, self.latched] {
+            for &register in &FOOTER_REGISTERS {
+                footer.extend_from_slice(&[registers.read(register), 0, 0, 0]);
+            }
+        }
+        for i in 0..8 {
+            footer.push((timestamp >> (i * 8)) as u8);
+        }
+        footer
+    }
+
+    /// Restore a clock from a 48 or 44-byte footer, returning it along with the timestamp it
+    /// was saved at. Returns `None` if the bytes are neither size
+    pub fn from_footer(bytes: &[u8]) -> Option<(Self, u64)> {
+        let timestamp_size = match bytes.len() {
+            FOOTER_SIZE => 8,
+            SHORT_FOOTER_SIZE => 4,
+            _ => return None,
+        };
+
+        let mut rtc = Rtc::default();
+        for (i, &register) in FOOTER_REGISTERS.iter().enumerate() {
+            rtc.live.write(register, bytes[i * 4]);
+            rtc.latched.write(register, bytes[(i + FOOTER_REGISTERS.len()) * 4]);
+        }
+        let timestamp = bytes[40..40 + timestamp_size]
+            .iter()
+            .rev()
+            .fold(0, |timestamp, &byte| timestamp << 8 | u64::from(byte));
+        Some((rtc, timestamp))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn counts_seconds_from_cycles() {
+        let mut rtc = Rtc::default();
+        rtc.emulate(CYCLES_PER_SECOND - 1);
+        assert_eq!(rtc.registers().seconds, 0);
+
+        rtc.emulate(1);
+        assert_eq!(rtc.registers().seconds, 1);
+
+        // nothing is visible until it is latched
+        assert_eq!(rtc.read(SECONDS), 0);
+        rtc.latch();
+        assert_eq!(rtc.read(SECONDS), 1);
+    }
+
+    #[test]
+    fn carries_into_days_and_overflows() {
+        let mut rtc = Rtc::default();
+        rtc.write(SECONDS, 59);
+        rtc.write(MINUTES, 59);
+        rtc.write(HOURS, 23);
+        rtc.write(DAYS_LOW, 0xFF);
+        rtc.write(DAYS_HIGH, 0x01);
+        rtc.emulate(CYCLES_PER_SECOND);
+        rtc.latch();
+
+        assert_eq!(rtc.read(HOURS), 0);
+        assert_eq!(rtc.read(DAYS_LOW), 0);
+        assert_eq!(rtc.read(DAYS_HIGH), DAY_CARRY_BIT);
+    }
+
+    #[test]
+    fn out_of_range_values_wrap_without_carry() {
+        let mut rtc = Rtc::default();
+        rtc.write(SECONDS, 63);
+        rtc.emulate(CYCLES_PER_SECOND);
+        assert_eq!(rtc.registers().seconds, 0);
+        assert_eq!(rtc.registers().minutes, 0);
+    }
+
+    #[test]
+    fn halt_stops_the_clock() {
+        let mut rtc = Rtc::default();
+        rtc.write(DAYS_HIGH, HALT_BIT);
+        rtc.emulate(CYCLES_PER_SECOND * 10);
+        assert_eq!(rtc.registers().seconds, 0);
+    }
+
+    #[test]
+    fn round_trips_through_footers() {
+        let mut rtc = Rtc::default();
+        rtc.write(HOURS, 23);
+        rtc.write(DAYS_LOW, 0xFF);
+        rtc.write(DAYS_HIGH, 0x01);
+        rtc.latch();
+
+        let footer = rtc.to_footer(0x0123_4567_89AB);
+        assert_eq!(footer.len(), FOOTER_SIZE);
+        let (mut restored, timestamp) = Rtc::from_footer(&footer).unwrap();
+        assert_eq!(timestamp, 0x0123_4567_89AB);
+        assert_eq!(restored.latched_registers(), rtc.latched_registers());
+
+        let (_, timestamp) = Rtc::from_footer(&footer[..SHORT_FOOTER_SIZE]).unwrap();
+        assert_eq!(timestamp, 0x4567_89AB);
+
+        // an hour and a day later, the day counter overflows
+        restored.advance(SECONDS_PER_DAY + 60 * 60);
+        assert_eq!(restored.registers().hours, 0);
+        assert_eq!(restored.registers().days, 1);
+        assert!(restored.registers().day_carry);
+    }
+}
@@ -0,0 +1,23 @@
+// Copyright 2018 Will Johnston
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Speakers on cartridges
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use isa::Word;
+
+/// A shared tone generator a cartridge plays sounds through
+pub type SpeakerPort = Rc<RefCell<dyn Speaker>>;
+
+/// A tone generator
+pub trait Speaker {
+    /// Play a tone, as numbered by the cartridge
+    fn play(&mut self, tone: Word);
+}
@@ -0,0 +1,264 @@
+// Copyright 2018 Will Johnston
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! TAMA5 real time clock
+//!
+//! The clock is a calendar read and written one decimal digit at a time, laid out like the
+//! TC8521 it is built around.
+
+use hardware::cpu::CYCLES_PER_SECOND;
+use isa::Word;
+
+const SECONDS_ONES: Word = 0x0;
+const SECONDS_TENS: Word = 0x1;
+const MINUTES_ONES: Word = 0x2;
+const MINUTES_TENS: Word = 0x3;
+const HOURS_ONES: Word = 0x4;
+const HOURS_TENS: Word = 0x5;
+const DAY_OF_WEEK: Word = 0x6;
+const DAY_ONES: Word = 0x7;
+const DAY_TENS: Word = 0x8;
+const MONTH_ONES: Word = 0x9;
+const MONTH_TENS: Word = 0xA;
+const YEAR_ONES: Word = 0xB;
+const YEAR_TENS: Word = 0xC;
+
+/// Number of bytes in a saved clock
+pub const SAVE_SIZE: usize = 7;
+
+/// A TAMA5 calendar clock, counting seconds from emulated cycles
+#[derive(Debug, Clone)]
+pub struct Tama5Clock {
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    /// Day of the week, from 0 to 6
+    day_of_week: u8,
+    /// Day of the month, from 1
+    day: u8,
+    /// Month, from 1
+    month: u8,
+    /// Year from 0 to 99. Every year divisible by 4 is a leap year
+    year: u8,
+    /// Cycles counted towards the next second
+    cycles: usize,
+}
+
+impl Tama5Clock {
+    /// Emulate the clock over a given number of cycles
+    pub fn emulate(&mut self, cycles: usize) {
+        self.cycles += cycles;
+        while self.cycles >= CYCLES_PER_SECOND {
+            self.cycles -= CYCLES_PER_SECOND;
+            self.tick();
+        }
+    }
+
+    /// Advance the clock by one second
+    fn tick(&mut self) {
+        self.seconds += 1;
+        if self.seconds < 60 {
+            return;
+        }
+        self.seconds = 0;
+
+        self.minutes += 1;
+        if self.minutes < 60 {
+            return;
+        }
+        self.minutes = 0;
+
+        self.hours += 1;
+        if self.hours < 24 {
+            return;
+        }
+        self.hours = 0;
+
+        self.day_of_week = (self.day_of_week + 1) % 7;
+        self.day += 1;
+        if self.day <= days_in_month(self.month, self.year) {
+            return;
+        }
+        self.day = 1;
+
+        self.month += 1;
+        if self.month <= 12 {
+            return;
+        }
+        self.month = 1;
+        self.year = (self.year + 1) % 100;
+    }
+
+    /// Read a digit of the clock by its register number
+    pub fn read(&self, register: Word) -> Word {
+        match register {
+            SECONDS_ONES => self.seconds % 10,
+            SECONDS_TENS => self.seconds / 10,
+            MINUTES_ONES => self.minutes % 10,
+            MINUTES_TENS => self.minutes / 10,
+            HOURS_ONES => self.hours % 10,
+            HOURS_TENS => self.hours / 10,
+            DAY_OF_WEEK => self.day_of_week,
+            DAY_ONES => self.day % 10,
+            DAY_TENS => self.day / 10,
+            MONTH_ONES => self.month % 10,
+            MONTH_TENS => self.month / 10,
+            YEAR_ONES => self.year % 10,
+            YEAR_TENS => self.year / 10,
+            _ => 0x0,
+        }
+    }
+
+    /// Write a digit of the clock by its register number. Values are kept in range, so a
+    /// write that would leave a field out of range is clamped
+    pub fn write(&mut self, register: Word, value: Word) {
+        let value = value & 0x0F;
+        let ones = |field: u8| field / 10 * 10 + value % 10;
+        let tens = |field: u8| value * 10 + field % 10;
+        match register {
+            SECONDS_ONES => self.seconds = ones(self.seconds).min(59),
+            SECONDS_TENS => self.seconds = tens(self.seconds).min(59),
+            MINUTES_ONES => self.minutes = ones(self.minutes).min(59),
+            MINUTES_TENS => self.minutes = tens(self.minutes).min(59),
+            HOURS_ONES => self.hours = ones(self.hours).min(23),
+            HOURS_TENS => self.hours = tens(self.hours).min(23),
+            DAY_OF_WEEK => self.day_of_week = value % 7,
+            DAY_ONES => self.day = ones(self.day),
+            DAY_TENS => self.day = tens(self.day),
+            MONTH_ONES => self.month = ones(self.month),
+            MONTH_TENS => self.month = tens(self.month),
+            YEAR_ONES => self.year = ones(self.year),
+            YEAR_TENS => self.year = tens(self.year),
+            _ => {}
+        }
+
+        self.month = self.month.clamp(1, 12);
+        self.day = self.day.clamp(1, days_in_month(self.month, self.year));
+        if register == SECONDS_ONES || register == SECONDS_TENS {
+            self.cycles = 0;
+        }
+    }
+
+    /// Serialize the clock as seconds, minutes, hours, day of the week, day, month and year
+    pub fn to_bytes(&self) -> Vec<u8> {
+        vec![
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.day_of_week,
+            self.day,
+            self.month,
+            self.year,
+        ]
+    }
+
+    /// Restore a clock serialized by `to_bytes`. Returns `None` if there are too few bytes or
+    /// they don't hold a valid time
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < SAVE_SIZE {
+            return None;
+        }
+
+        let clock = Tama5Clock {
+            seconds: bytes[0],
+            minutes: bytes[1],
+            hours: bytes[2],
+            day_of_week: bytes[3],
+            day: bytes[4],
+            month: bytes[5],
+            year: bytes[6],
+            cycles: 0,
+        };
+
+        let valid = clock.seconds < 60
+            && clock.minutes < 60
+            && clock.hours < 24
+            && clock.day_of_week < 7
+            && clock.year < 100
+            && clock.month >= 1
+            && clock.month <= 12
+            && clock.day >= 1
+            && clock.day <= days_in_month(clock.month, clock.year);
+        if valid {
+            Some(clock)
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for Tama5Clock {
+    fn default() -> Self {
+        Tama5Clock {
+            seconds: 0,
+            minutes: 0,
+            hours: 0,
+            day_of_week: 0,
+            day: 1,
+            month: 1,
+            year: 0,
+            cycles: 0,
+        }
+    }
+}
+
+/// Return the number of days in a month
+fn days_in_month(month: u8, year: u8) -> u8 {
+    match month {
+        2 if year.is_multiple_of(4) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn set(clock: &mut Tama5Clock, digits: &[(Word, Word)]) {
+        for &(register, value) in digits {
+            clock.write(register, value);
+        }
+    }
+
+    #[test]
+    fn rolls_over_the_year() {
+        let mut clock = Tama5Clock::default();
+        set(
+            &mut clock,
+            &[
+                (YEAR_TENS, 9),
+                (YEAR_ONES, 9),
+                (MONTH_TENS, 1),
+                (MONTH_ONES, 2),
+                (DAY_TENS, 3),
+                (DAY_ONES, 1),
+                (HOURS_TENS, 2),
+                (HOURS_ONES, 3),
+                (MINUTES_TENS, 5),
+                (MINUTES_ONES, 9),
+                (SECONDS_TENS, 5),
+                (SECONDS_ONES, 9),
+            ],
+        );
+        clock.emulate(CYCLES_PER_SECOND);
+        assert_eq!(clock.to_bytes(), vec![0, 0, 0, 1, 1, 1, 0]);
+    }
+
+    #[test]
+    fn knows_leap_years() {
+        let mut clock = Tama5Clock::default();
+        set(&mut clock, &[(YEAR_ONES, 4), (MONTH_ONES, 2), (DAY_TENS, 2), (DAY_ONES, 8)]);
+        clock.emulate(CYCLES_PER_SECOND * 60 * 60 * 24);
+        assert_eq!((clock.read(MONTH_ONES), clock.read(DAY_TENS)), (2, 2));
+        assert_eq!(clock.read(DAY_ONES), 9);
+        assert!(Tama5Clock::from_bytes(&clock.to_bytes()).is_some());
+        assert!(Tama5Clock::from_bytes(&[0, 0, 0, 0, 30, 2, 4]).is_none());
+    }
+}
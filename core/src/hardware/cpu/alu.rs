@@ -0,0 +1,267 @@
+// Copyright 2018 Will Johnston
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! 8-bit arithmetic logic unit
+//!
+//! Every operation takes its operands, writes the `Z`, `N`, `H` and `C` flags to the register
+//! file exactly as the hardware does, and returns the result. Storing the result is left to
+//! the caller.
+
+use super::Registers;
+use isa::{Flag, Word};
+
+/// Mask selecting the carry out of bit 3 after XOR-ing operands with their result
+const HALF_CARRY_MASK: u16 = 0x10;
+
+/// Mask selecting the carry out of bit 7 after XOR-ing operands with their result
+const CARRY_MASK: u16 = 0x100;
+
+/// Write all four flags at once
+fn write_flags(registers: &mut Registers, zero: bool, sub: bool, half: bool, carry: bool) {
+    registers.write_flag(Flag::Zero, zero);
+    registers.write_flag(Flag::AddSub, sub);
+    registers.write_flag(Flag::HalfCarry, half);
+    registers.write_flag(Flag::Carry, carry);
+}
+
+/// Add `value` and an optional carry to `a`
+pub fn add(registers: &mut Registers, a: Word, value: Word, carry: bool) -> Word {
+    let sum = u16::from(a) + u16::from(value) + u16::from(carry);
+    let carries = u16::from(a) ^ u16::from(value) ^ sum;
+    let result = sum as Word;
+
+    write_flags(
+        registers,
+        result == 0,
+        false,
+        carries & HALF_CARRY_MASK != 0,
+        carries & CARRY_MASK != 0,
+    );
+    result
+}
+
+/// Subtract `value` and an optional carry from `a`
+pub fn sub(registers: &mut Registers, a: Word, value: Word, carry: bool) -> Word {
+    let difference = u16::from(a)
+        .wrapping_sub(u16::from(value))
+        .wrapping_sub(u16::from(carry));
+    let borrows = u16::from(a) ^ u16::from(value) ^ difference;
+    let result = difference as Word;
+
+    write_flags(
+        registers,
+        result == 0,
+        true,
+        borrows & HALF_CARRY_MASK != 0,
+        borrows & CARRY_MASK != 0,
+    );
+    result
+}
+
+/// Compare `value` against `a`. Flags are set as `sub` would but nothing is returned
+pub fn cp(registers: &mut Registers, a: Word, value: Word) {
+    sub(registers, a, value, false);
+}
+
+/// Bitwise and of `a` and `value`
+pub fn and(registers: &mut Registers, a: Word, value: Word) -> Word {
+    let result = a & value;
+    write_flags(registers, result == 0, false, true, false);
+    result
+}
+
+/// Bitwise or of `a` and `value`
+pub fn or(registers: &mut Registers, a: Word, value: Word) -> Word {
+    let result = a | value;
+    write_flags(registers, result == 0, false, false, false);
+    result
+}
+
+/// Bitwise exclusive or of `a` and `value`
+pub fn xor(registers: &mut Registers, a: Word, value: Word) -> Word {
+    let result = a ^ value;
+    write_flags(registers, result == 0, false, false, false);
+    result
+}
+
+/// Increment a value. The carry flag is left untouched
+pub fn inc(registers: &mut Registers, value: Word) -> Word {
+    let result = value.wrapping_add(1);
+    registers.write_flag(Flag::Zero, result == 0);
+    registers.write_flag(Flag::AddSub, false);
+    registers.write_flag(Flag::HalfCarry, value & 0x0F == 0x0F);
+    result
+}
+
+/// Decrement a value. The carry flag is left untouched
+pub fn dec(registers: &mut Registers, value: Word) -> Word {
+    let result = value.wrapping_sub(1);
+    registers.write_flag(Flag::Zero, result == 0);
+    registers.write_flag(Flag::AddSub, true);
+    registers.write_flag(Flag::HalfCarry, value & 0x0F == 0x00);
+    result
+}
+
+/// Adjust `a` back into binary coded decimal after an addition or subtraction, using the
+/// `N`, `H` and `C` flags left behind by that operation
+pub fn daa(registers: &mut Registers, a: Word) -> Word {
+    let subtract = registers.read_flag(Flag::AddSub);
+    let half = registers.read_flag(Flag::HalfCarry);
+    let mut carry = registers.read_flag(Flag::Carry);
+    let mut adjust = 0;
+
+    if half || (!subtract && a & 0x0F > 0x09) {
+        adjust |= 0x06;
+    }
+
+    if carry || (!subtract && a > 0x99) {
+        adjust |= 0x60;
+        carry = true;
+    }
+
+    let result = if subtract {
+        a.wrapping_sub(adjust)
+    } else {
+        a.wrapping_add(adjust)
+    };
+
+    write_flags(registers, result == 0, subtract, false, carry);
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Flags as computed by the reference model, in the order `(Z, N, H, C)`
+    type Flags = (bool, bool, bool, bool);
+
+    fn flags(registers: &Registers) -> Flags {
+        (
+            registers.read_flag(Flag::Zero),
+            registers.read_flag(Flag::AddSub),
+            registers.read_flag(Flag::HalfCarry),
+            registers.read_flag(Flag::Carry),
+        )
+    }
+
+    /// Reference model of an 8-bit add with carry, done on nibbles with wide integers
+    fn reference_add(a: Word, value: Word, carry: bool) -> (Word, Flags) {
+        let carry = carry as u32;
+        let full = u32::from(a) + u32::from(value) + carry;
+        let low = u32::from(a & 0x0F) + u32::from(value & 0x0F) + carry;
+        let result = (full % 256) as Word;
+        (result, (result == 0, false, low > 0x0F, full > 0xFF))
+    }
+
+    /// Reference model of an 8-bit subtract with carry, done with signed integers
+    fn reference_sub(a: Word, value: Word, carry: bool) -> (Word, Flags) {
+        let carry = carry as i32;
+        let full = i32::from(a) - i32::from(value) - carry;
+        let low = i32::from(a & 0x0F) - i32::from(value & 0x0F) - carry;
+        let result = ((full + 256) % 256) as Word;
+        (result, (result == 0, true, low < 0, full < 0))
+    }
+
+    fn to_bcd(n: u8) -> Word {
+        ((n / 10) << 4) | (n % 10)
+    }
+
+    fn registers_with_carry(carry: bool) -> Registers {
+        let mut registers = Registers::default();
+        registers.write_flag(Flag::Carry, carry);
+        registers
+    }
+
+    quickcheck! {
+        fn add_matches_reference(a: Word, value: Word, carry: bool) -> bool {
+            let mut registers = Registers::default();
+            let result = add(&mut registers, a, value, carry);
+            (result, flags(&registers)) == reference_add(a, value, carry)
+        }
+
+        fn sub_matches_reference(a: Word, value: Word, carry: bool) -> bool {
+            let mut registers = Registers::default();
+            let result = sub(&mut registers, a, value, carry);
+            (result, flags(&registers)) == reference_sub(a, value, carry)
+        }
+
+        fn cp_sets_flags_like_sub_and_returns_nothing(a: Word, value: Word) -> bool {
+            let mut registers = Registers::default();
+            cp(&mut registers, a, value);
+            flags(&registers) == reference_sub(a, value, false).1
+        }
+
+        fn logic_ops_match_reference(a: Word, value: Word) -> bool {
+            let mut registers = Registers::default();
+            let and_result = and(&mut registers, a, value);
+            let and_flags = flags(&registers);
+            let or_result = or(&mut registers, a, value);
+            let or_flags = flags(&registers);
+            let xor_result = xor(&mut registers, a, value);
+            let xor_flags = flags(&registers);
+
+            and_result == a & value
+                && and_flags == (a & value == 0, false, true, false)
+                && or_result == a | value
+                && or_flags == (a | value == 0, false, false, false)
+                && xor_result == a ^ value
+                && xor_flags == (a ^ value == 0, false, false, false)
+        }
+
+        fn inc_matches_add_and_keeps_carry(value: Word, carry: bool) -> bool {
+            let mut registers = registers_with_carry(carry);
+            let result = inc(&mut registers, value);
+            let (expected, (z, n, h, _)) = reference_add(value, 1, false);
+            (result, flags(&registers)) == (expected, (z, n, h, carry))
+        }
+
+        fn dec_matches_sub_and_keeps_carry(value: Word, carry: bool) -> bool {
+            let mut registers = registers_with_carry(carry);
+            let result = dec(&mut registers, value);
+            let (expected, (z, n, h, _)) = reference_sub(value, 1, false);
+            (result, flags(&registers)) == (expected, (z, n, h, carry))
+        }
+
+        fn daa_after_add_is_decimal_add(x: u8, y: u8) -> bool {
+            let (x, y) = (x % 100, y % 100);
+            let mut registers = Registers::default();
+            let binary = add(&mut registers, to_bcd(x), to_bcd(y), false);
+            let result = daa(&mut registers, binary);
+            let sum = x + y;
+            result == to_bcd(sum % 100)
+                && flags(&registers) == (sum % 100 == 0, false, false, sum >= 100)
+        }
+
+        fn daa_after_sub_is_decimal_sub(x: u8, y: u8) -> bool {
+            let (x, y) = (x % 100, y % 100);
+            let mut registers = Registers::default();
+            let binary = sub(&mut registers, to_bcd(x), to_bcd(y), false);
+            let result = daa(&mut registers, binary);
+            let difference = (100 + x - y) % 100;
+            result == to_bcd(difference)
+                && flags(&registers) == (difference == 0, true, false, x < y)
+        }
+    }
+
+    #[test]
+    fn half_carry_examples() {
+        let mut registers = Registers::default();
+        assert_eq!(add(&mut registers, 0x0F, 0x01, false), 0x10);
+        assert!(registers.read_flag(Flag::HalfCarry));
+        assert!(!registers.read_flag(Flag::Carry));
+
+        assert_eq!(sub(&mut registers, 0x10, 0x01, false), 0x0F);
+        assert!(registers.read_flag(Flag::HalfCarry));
+        assert!(!registers.read_flag(Flag::Carry));
+
+        cp(&mut registers, 0x3C, 0x2F);
+        assert!(registers.read_flag(Flag::HalfCarry));
+        assert!(!registers.read_flag(Flag::Zero));
+    }
+}
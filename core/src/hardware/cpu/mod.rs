@@ -11,54 +11,128 @@
 mod registers;
 pub use self::registers::Registers;
 
+mod alu;
+
 use disasm::decode;
-use hardware::bios::Bios;
+use hardware::interrupt::{self, Interrupts, DISPATCH_CYCLES};
+use hardware::memory::addresses::registers::{INTERRUPT_ENABLE, INTERRUPT_FLAG};
 use hardware::memory::Memory;
 use isa::{
-    Address, Flag, Immediate16, Immediate8, Instruction, Register16, Register8, SignedImmediate8,
-    Word,
+    Address, Condition, DoubleWord, Flag, Immediate16, Immediate8, Instruction, Register16,
+    Register8, SignedImmediate8, Word,
 };
 use std::fmt;
 
 pub const CYCLES_PER_SECOND: usize = 4_194_304;
 pub const BIOS_BUFFER_SIZE: usize = 0x900;
 
+/// Number of cycles that pass in one step while the CPU is halted or stopped
+pub const IDLE_CYCLES: u8 = 4;
+
 /// A Gameboy central processing unit
 #[derive(Clone, Copy)]
-pub struct Cpu<B: Bios> {
+pub struct Cpu {
     ime: bool,
+    ime_scheduled: bool,
     is_halted: bool,
-    is_bios_disabled: bool,
-    bios: B,
+    is_stopped: bool,
+    halt_bug: bool,
     registers: Registers,
 }
 
-impl<B: Bios> Cpu<B> {
-    pub fn new(bios: B) -> Self {
+impl Cpu {
+    pub fn new() -> Self {
+        Self::with_registers(Registers::default())
+    }
+
+    /// Create a new CPU with its register file in a given state
+    pub fn with_registers(registers: Registers) -> Self {
         Cpu {
             // interrupt master enable
             ime: false,
-            bios,
-            is_bios_disabled: false,
+            // `ei` enables interrupts only after the instruction following it
+            ime_scheduled: false,
             is_halted: false,
-            registers: Registers::default(),
+            is_stopped: false,
+            halt_bug: false,
+            registers,
         }
     }
 
     /// Execute the current instruction and advance the CPU forward one step, Returns the
     /// number of cycles used
     pub fn step<M: Memory>(&mut self, memory: &mut M) -> u8 {
-        // read in raw value of instruction into ir
-        let instruction = decode(memory, self.registers.pc);
+        if self.is_halted || self.is_stopped {
+            trace!("CPU is idle");
+            return IDLE_CYCLES;
+        }
+
+        let pc = self.registers.pc;
+        let (instruction, size) = if self.halt_bug {
+            // PC failed to increment after the opcode fetch, so the opcode byte is read again
+            // as the start of its own operands
+            self.halt_bug = false;
+            let instruction = decode(&HaltBugView { memory, pc }, pc);
+            (instruction, instruction.size() - 1)
+        } else {
+            let instruction = decode(memory, pc);
+            (instruction, instruction.size())
+        };
         trace!("Fetched instruction {:?}", instruction);
         trace!(
             "Advancing program counter from {} to {}",
-            self.registers.pc,
-            self.registers.pc + Address::from(instruction.size())
+            pc,
+            pc.wrapping_add(Address::from(size))
         );
-        self.registers.pc += Address::from(instruction.size());
+        self.registers.pc = pc.wrapping_add(Address::from(size));
+
+        let enable_interrupts = self.ime_scheduled;
+        let cycles = self.execute(instruction, memory);
+        if enable_interrupts && self.ime_scheduled {
+            self.ime = true;
+            self.ime_scheduled = false;
+        }
+
+        cycles
+    }
+
+    /// Dispatch the highest priority pending interrupt, if interrupts are enabled, and return
+    /// the number of cycles used
+    ///
+    /// A pending interrupt wakes a halted CPU whether or not it is serviced
+    pub fn service_interrupts<M: Memory>(&mut self, memory: &mut M) -> u8 {
+        let requested = interrupt::from_register(memory.read(INTERRUPT_FLAG));
+        let pending = requested & Self::enabled_interrupts(memory);
+
+        let next = match interrupt::highest_priority(pending) {
+            Some(next) => next,
+            None => return 0,
+        };
+
+        self.is_halted = false;
+        if !self.ime {
+            return 0;
+        }
+
+        debug!("Dispatching {:?} interrupt", next);
+        self.ime = false;
+        memory.write(INTERRUPT_FLAG, interrupt::to_register(requested - next));
+
+        let pc = self.registers.pc;
+        self.push(pc, memory);
+        self.registers.pc = next.vector();
+
+        DISPATCH_CYCLES
+    }
+
+    /// Return the interrupts enabled in `IE`
+    fn enabled_interrupts<M: Memory>(memory: &M) -> Interrupts {
+        interrupt::from_register(memory.read(INTERRUPT_ENABLE))
+    }
 
-        self.execute(instruction, memory)
+    /// Returns true if interrupts are enabled
+    pub fn ime(&self) -> bool {
+        self.ime
     }
 
     /// Return a reference to the CPU's registers
@@ -71,41 +145,143 @@ impl<B: Bios> Cpu<B> {
         self.is_halted
     }
 
+    /// Returns true if the `[CPU]` is in the low power mode entered by `stop`
+    pub fn is_stopped(&self) -> bool {
+        self.is_stopped
+    }
+
+    /// Leave the low power mode entered by `stop`
+    pub fn resume(&mut self) {
+        trace!("Resuming from stop");
+        self.is_stopped = false;
+    }
+
     /// Execute an instruction and return the number of cycles used
     fn execute<M: Memory>(&mut self, instruction: Instruction, memory: &mut M) -> u8 {
         trace!("Entering execution phase");
         use self::Instruction::*;
         let did_branch = match instruction {
-            Nop => self.exectue_nop(),
+            // 8-bit load instructions
+            LdRR(a, b) => self.execute_ld_r_r(a, b),
+            LdRI(register, immediate) => self.execute_ld_r_i(register, immediate),
+            LdRHl(register) => self.execute_ld_r_hl(register, memory),
+            LdHlR(register) => self.execute_ld_hl_r(register, memory),
+            LdHlI(immediate) => self.execute_ld_hl_i(immediate, memory),
+            LdARr(register) => self.execute_ld_a_rr(register, memory),
+            LdAI(immediate) => self.execute_ld_a_i(immediate),
+            LdRrA(register) => self.execute_ld_rr_a(register, memory),
+            LdAIi(address) => self.execute_ld_a_ii(address, memory),
+            LdAIo(immediate) => self.execute_ld_a_io(immediate, memory),
+            LdIoA(immediate) => self.execute_ld_io_a(immediate, memory),
+            LdIiA(address) => self.execute_ld_ii_a(address, memory),
+            LdAIoc => self.execute_ld_a_ioc(memory),
+            LdIocA => self.execute_ld_ioc_a(memory),
+            LdiHlA => self.execute_ldi_hl_a(memory),
+            LdiAHl => self.execute_ldi_a_hl(memory),
+            LddHlA => self.execute_ldd_hl_a(memory),
+            LddAHl => self.execute_ldd_a_hl(memory),
+
+            // 16-bit load instructions
             LdRrIi(register, immediate) => self.execute_ld_rr_ii(register, immediate),
+            LdSpHl => self.execute_ld_sp_hl(),
+            LdIiSp(address) => self.execute_ld_ii_sp(address, memory),
+            PushRr(register) => self.execute_push_rr(register, memory),
+            Pop(register) => self.execute_pop(register, memory),
+
+            // 8-bit ALU instructions
+            AddAR(register) => self.execute_add_a_r(register),
+            AddAI(immediate) => self.execute_add_a_i(immediate),
+            AddAHl => self.execute_add_a_hl(memory),
+            AdcAR(register) => self.execute_adc_a_r(register),
+            AdcAI(immediate) => self.execute_adc_a_i(immediate),
+            AdcAHl => self.execute_adc_a_hl(memory),
+            SubAR(register) => self.execute_sub_a_r(register),
+            SubAI(immediate) => self.execute_sub_a_i(immediate),
+            SubAHl => self.execute_sub_a_hl(memory),
+            SbcAR(register) => self.execute_sbc_a_r(register),
+            SbcAI(immediate) => self.execute_sbc_a_i(immediate),
+            SbcAHl => self.execute_sbc_a_hl(memory),
+            AndAR(register) => self.execute_and_a_r(register),
+            AndAI(immediate) => self.execute_and_a_i(immediate),
+            AndAHl => self.execute_and_a_hl(memory),
             XorAR(register) => self.execute_xor_a_r(register),
             XorAI(immediate) => self.execute_xor_a_i(immediate),
-            AndAR(register) => self.execute_and_a_r(register),
+            XorAHl => self.execute_xor_a_hl(memory),
+            OrAR(register) => self.execute_or_a_r(register),
+            OrAI(immediate) => self.execute_or_a_i(immediate),
+            OrAHl => self.execute_or_a_hl(memory),
+            CpAR(register) => self.execute_cp_a_r(register),
+            CpAI(immediate) => self.execute_cp_a_i(immediate),
+            CpAHl => self.execute_cp_a_hl(memory),
+            IncR(register) => self.execute_inc_r(register),
+            IncHl => self.execute_inc_hl(memory),
+            DecR(register) => self.execute_dec_r(register),
+            DecHl => self.execute_dec_hl(memory),
+            Daa => self.execute_daa(),
+            Cpl => self.execute_cpl(),
+
+            // 16-bit ALU instructions
+            AddHlRr(register) => self.execute_add_hl_rr(register),
+            AddSpS(offset) => self.execute_add_sp_s(offset),
+            IncRr(register) => self.execute_inc_rr(register),
+            DecRr(register) => self.execute_dec_rr(register),
+            LdHlSpS(offset) => self.execute_ld_hl_sp_s(offset),
+
+            // Rotate/shift commands
+            Rlca => self.execute_rlca(),
+            Rla => self.execute_rla(),
+            Rrca => self.execute_rrca(),
+            Rra => self.execute_rra(),
+            RlcR(register) => self.execute_rlc_r(register),
+            RlcHl => self.execute_rlc_hl(memory),
+            RlR(register) => self.execute_rl_r(register),
+            RlHl => self.execute_rl_hl(memory),
+            RrcR(register) => self.execute_rrc_r(register),
+            RrcHl => self.execute_rrc_hl(memory),
+            RrR(register) => self.execute_rr_r(register),
+            RrHl => self.execute_rr_hl(memory),
+            SlaR(register) => self.execute_sla_r(register),
+            SlaHl => self.execute_sla_hl(memory),
+            SwapR(register) => self.execute_swap_r(register),
+            SwapHl => self.execute_swap_hl(memory),
+            SraR(register) => self.execute_sra_r(register),
+            SraHl => self.execute_sra_hl(memory),
+            SrlR(register) => self.execute_srl_r(register),
+            SrlHl => self.execute_srl_hl(memory),
+
+            // Single-bit commands
             BitIR(immediate, register) => self.execute_bit_i_r(immediate, register),
-            LddAHl => self.execute_ldd_a_hl(memory),
-            LddHlA => self.execute_ldd_hl_a(memory),
-            LdHlR(register) => self.execute_ld_hl_r(register, memory),
+            BitIHl(immediate) => self.execute_bit_i_hl(immediate, memory),
+            SetIR(immediate, register) => self.execute_set_i_r(immediate, register),
+            SetIHl(immediate) => self.execute_set_i_hl(immediate, memory),
+            ResIR(immediate, register) => self.execute_res_i_r(immediate, register),
+            ResIHl(immediate) => self.execute_res_i_hl(immediate, memory),
+
+            // Control commands
+            Ccf => self.execute_ccf(),
+            Scf => self.execute_scf(),
+            Nop => self.exectue_nop(),
+            Halt => self.execute_halt(memory),
+            Stop => self.execute_stop(),
             Di => self.execute_di(),
             Ei => self.execute_ei(),
-            LdIocA => self.execute_ld_ioc_a(memory),
-            LdAI(immediate) => self.execute_ld_a_i(immediate),
-            LdRI(register, immediate) => self.execute_ld_r_i(register, immediate),
-            LdIoA(immediate) => self.execute_ld_io_a(immediate, memory),
-            LdAIo(immediate) => self.execute_ld_a_io(immediate, memory),
-            AddAR(register) => self.execute_add_a_r(register),
+
+            // Jump commands
+            JpIi(address) => self.execute_jp_ii(address),
+            JpCondIi(condition, address) => self.execute_jp_cond_ii(condition, address),
+            JpHl => self.execute_jp_hl(),
+            JrS(offset) => self.execute_jr_s(offset),
             JrCondS(condition, offset) => self.exectue_jr_cond_s(condition, offset),
-            LdRR(a, b) => self.execute_ld_r_r(a, b),
-            LdARr(register) => self.execute_ld_a_rr(register, memory),
             CallIi(address) => self.execute_call_ii(address, memory),
-            IncRr(register) => self.execute_inc_rr(register),
-            IncR(register) => self.execute_inc_r(register),
-            DecRr(register) => self.execute_dec_rr(register),
-            DecR(register) => self.execute_dec_r(register),
-            CpAI(immediate) => self.execute_cp_a_i(immediate),
-            PushRr(register) => self.execute_push_rr(register, memory),
-            v => {
-                unimplemented!("{:?} instruction not implemented", v);
+            CallCondIi(condition, address) => {
+                self.execute_call_cond_ii(condition, address, memory)
             }
+            Ret => self.execute_ret(memory),
+            RetCond(condition) => self.execute_ret_cond(condition, memory),
+            Reti => self.execute_reti(memory),
+            Rst(vector) => self.execute_rst(vector, memory),
+
+            Undefined(opcode) => self.execute_undefined(opcode),
         };
 
         if did_branch {
@@ -115,6 +291,103 @@ impl<B: Bios> Cpu<B> {
         }
     }
 
+    /// Push a `DoubleWord` onto the stack
+    fn push<M: Memory>(&mut self, value: DoubleWord, memory: &mut M) {
+        self.registers.sp = self.registers.sp.wrapping_sub(2);
+        memory.write_double(self.registers.sp, value);
+    }
+
+    /// Pop a `DoubleWord` off of the stack
+    fn pop<M: Memory>(&mut self, memory: &M) -> DoubleWord {
+        let value = memory.read_double(self.registers.sp);
+        self.registers.sp = self.registers.sp.wrapping_add(2);
+        value
+    }
+
+    /// Return `SP` plus a signed offset, setting flags from the low byte addition
+    fn sp_plus_offset(&mut self, offset: SignedImmediate8) -> DoubleWord {
+        let sp = self.registers.sp;
+        let offset = offset as i16 as u16;
+
+        self.registers.write_flag(Flag::Zero, false);
+        self.registers.write_flag(Flag::AddSub, false);
+        self.registers
+            .write_flag(Flag::HalfCarry, (sp & 0x000F) + (offset & 0x000F) > 0x000F);
+        self.registers
+            .write_flag(Flag::Carry, (sp & 0x00FF) + (offset & 0x00FF) > 0x00FF);
+        sp.wrapping_add(offset)
+    }
+
+    /// Set flags for a rotate or shift and return the result
+    fn shifted(&mut self, result: Word, carry: bool) -> Word {
+        self.registers.write_flag(Flag::Zero, result == 0);
+        self.registers.write_flag(Flag::AddSub, false);
+        self.registers.write_flag(Flag::HalfCarry, false);
+        self.registers.write_flag(Flag::Carry, carry);
+        result
+    }
+
+    fn rlc(&mut self, value: Word) -> Word {
+        self.shifted(value.rotate_left(1), value & 0x80 != 0)
+    }
+
+    fn rl(&mut self, value: Word) -> Word {
+        let carry_in = self.registers.read_flag(Flag::Carry) as Word;
+        self.shifted((value << 1) | carry_in, value & 0x80 != 0)
+    }
+
+    fn rrc(&mut self, value: Word) -> Word {
+        self.shifted(value.rotate_right(1), value & 0x01 != 0)
+    }
+
+    fn rr(&mut self, value: Word) -> Word {
+        let carry_in = self.registers.read_flag(Flag::Carry) as Word;
+        self.shifted((value >> 1) | (carry_in << 7), value & 0x01 != 0)
+    }
+
+    fn sla(&mut self, value: Word) -> Word {
+        self.shifted(value << 1, value & 0x80 != 0)
+    }
+
+    fn sra(&mut self, value: Word) -> Word {
+        self.shifted((value >> 1) | (value & 0x80), value & 0x01 != 0)
+    }
+
+    fn srl(&mut self, value: Word) -> Word {
+        self.shifted(value >> 1, value & 0x01 != 0)
+    }
+
+    fn swap(&mut self, value: Word) -> Word {
+        self.shifted(value.rotate_left(4), false)
+    }
+
+    /// Apply a read-modify-write operation to the value at `(HL)`
+    fn modify_hl<M, F>(&mut self, memory: &mut M, f: F)
+    where
+        M: Memory,
+        F: FnOnce(&mut Self, Word) -> Word,
+    {
+        let hl_val = self.registers.read_register16(Register16::HL);
+        let value = memory.read(hl_val);
+        let result = f(self, value);
+        memory.write(hl_val, result);
+    }
+
+    /// Apply a read-modify-write operation to an 8-bit register
+    fn modify_r<F>(&mut self, register: Register8, f: F)
+    where
+        F: FnOnce(&mut Self, Word) -> Word,
+    {
+        let value = self.registers.read_register8(register);
+        let result = f(self, value);
+        self.registers.write_register8(register, result);
+    }
+
+    /// Return the value at `(HL)`
+    fn read_hl<M: Memory>(&self, memory: &M) -> Word {
+        memory.read(self.registers.read_register16(Register16::HL))
+    }
+
     #[inline]
     fn exectue_nop(&mut self) -> bool {
         trace!("Executing nop");
@@ -129,7 +402,8 @@ impl<B: Bios> Cpu<B> {
         memory.write(hl_val, a_val);
 
         // decrement HL
-        self.registers.write_register16(Register16::HL, hl_val - 1);
+        self.registers
+            .write_register16(Register16::HL, hl_val.wrapping_sub(1));
         false
     }
 
@@ -139,16 +413,56 @@ impl<B: Bios> Cpu<B> {
         let hl_val = self.registers.read_register16(Register16::HL);
         self.registers
             .write_register8(Register8::A, memory.read(hl_val));
-        self.registers.write_register16(Register16::HL, hl_val - 1);
+        self.registers
+            .write_register16(Register16::HL, hl_val.wrapping_sub(1));
         false
     }
 
     #[inline]
-    fn execute_ld_hl_r<M: Memory>(&mut self, register: Register8, memory: &M) -> bool {
-        trace!("Executing ld (HL) {}", register);
+    fn execute_ldi_hl_a<M: Memory>(&mut self, memory: &mut M) -> bool {
+        trace!("Executing ldi (HL) A");
+        let a_val = self.registers.read_register8(Register8::A);
+        let hl_val = self.registers.read_register16(Register16::HL);
+        memory.write(hl_val, a_val);
+
+        // increment HL
+        self.registers
+            .write_register16(Register16::HL, hl_val.wrapping_add(1));
+        false
+    }
+
+    #[inline]
+    fn execute_ldi_a_hl<M: Memory>(&mut self, memory: &M) -> bool {
+        trace!("Executing ldi A (HL)");
         let hl_val = self.registers.read_register16(Register16::HL);
         self.registers
             .write_register8(Register8::A, memory.read(hl_val));
+        self.registers
+            .write_register16(Register16::HL, hl_val.wrapping_add(1));
+        false
+    }
+
+    #[inline]
+    fn execute_ld_hl_r<M: Memory>(&mut self, register: Register8, memory: &mut M) -> bool {
+        trace!("Executing ld (HL) {}", register);
+        let hl_val = self.registers.read_register16(Register16::HL);
+        memory.write(hl_val, self.registers.read_register8(register));
+        false
+    }
+
+    #[inline]
+    fn execute_ld_r_hl<M: Memory>(&mut self, register: Register8, memory: &M) -> bool {
+        trace!("Executing ld {} (HL)", register);
+        let value = self.read_hl(memory);
+        self.registers.write_register8(register, value);
+        false
+    }
+
+    #[inline]
+    fn execute_ld_hl_i<M: Memory>(&mut self, immediate: Immediate8, memory: &mut M) -> bool {
+        trace!("Executing ld (HL) {}", immediate);
+        let hl_val = self.registers.read_register16(Register16::HL);
+        memory.write(hl_val, immediate);
         false
     }
 
@@ -156,23 +470,31 @@ impl<B: Bios> Cpu<B> {
     fn execute_di(&mut self) -> bool {
         trace!("Executing di");
         self.ime = false;
+        self.ime_scheduled = false;
         false
     }
 
     #[inline]
     fn execute_ei(&mut self) -> bool {
         trace!("Executing ei");
-        self.ime = true;
+        self.ime_scheduled = true;
         false
     }
 
     #[inline]
-    fn execute_ld_ioc_a<M: Memory>(&mut self, memory: &M) -> bool {
-        trace!("TODO: annotate this correctyl");
+    fn execute_ld_a_ioc<M: Memory>(&mut self, memory: &M) -> bool {
+        trace!("Executing ld A (C)");
         self.registers.a = memory.read(0xFF00 + Address::from(self.registers.c));
         false
     }
 
+    #[inline]
+    fn execute_ld_ioc_a<M: Memory>(&mut self, memory: &mut M) -> bool {
+        trace!("Executing ld (C) A");
+        memory.write(0xFF00 + Address::from(self.registers.c), self.registers.a);
+        false
+    }
+
     #[inline]
     fn execute_ld_a_i(&mut self, immediate: Immediate8) -> bool {
         trace!("Executing ld a {}", immediate);
@@ -188,13 +510,44 @@ impl<B: Bios> Cpu<B> {
     }
 
     #[inline]
-    fn exectue_jr_cond_s(&mut self, condition: Flag, offset: SignedImmediate8) -> bool {
+    fn execute_jp_ii(&mut self, address: Address) -> bool {
+        trace!("Executing jp {}", address);
+        self.registers.pc = address;
+        false
+    }
+
+    #[inline]
+    fn execute_jp_cond_ii(&mut self, condition: Condition, address: Address) -> bool {
+        trace!("Executing jp {} {}", condition, address);
+        if self.registers.check_condition(condition) {
+            self.registers.pc = address;
+            true
+        } else {
+            false
+        }
+    }
+
+    #[inline]
+    fn execute_jp_hl(&mut self) -> bool {
+        trace!("Executing jp HL");
+        self.registers.pc = self.registers.read_register16(Register16::HL);
+        false
+    }
+
+    #[inline]
+    fn execute_jr_s(&mut self, offset: SignedImmediate8) -> bool {
+        trace!("Executing jr {}", offset);
+        self.registers.pc = self.registers.pc.wrapping_add(offset as i16 as u16);
+        false
+    }
+
+    #[inline]
+    fn exectue_jr_cond_s(&mut self, condition: Condition, offset: SignedImmediate8) -> bool {
         trace!("Executing jr {} {}", condition, offset);
-        if self.registers.read_flag(condition) {
-            debug!("Instruction not implemented");
+        if self.registers.check_condition(condition) {
+            self.registers.pc = self.registers.pc.wrapping_add(offset as i16 as u16);
             true
         } else {
-            debug!("Instruction not implemented");
             false
         }
     }
@@ -202,9 +555,51 @@ impl<B: Bios> Cpu<B> {
     #[inline]
     fn execute_bit_i_r(&mut self, immediate: Immediate8, register: Register8) -> bool {
         trace!("Executing bit {} {}", immediate, register);
-        debug!("instruction not implemented");
+        let value = self.registers.read_register8(register);
+        self.test_bit(immediate, value);
+        false
+    }
+
+    #[inline]
+    fn execute_bit_i_hl<M: Memory>(&mut self, immediate: Immediate8, memory: &M) -> bool {
+        trace!("Executing bit {} (HL)", immediate);
+        let value = self.read_hl(memory);
+        self.test_bit(immediate, value);
+        false
+    }
+
+    /// Set flags from testing bit `n` of a value
+    fn test_bit(&mut self, n: Immediate8, value: Word) {
+        self.registers.write_flag(Flag::Zero, value & (1 << n) == 0);
+        self.registers.write_flag(Flag::AddSub, false);
+        self.registers.write_flag(Flag::HalfCarry, true);
+    }
+
+    #[inline]
+    fn execute_set_i_r(&mut self, immediate: Immediate8, register: Register8) -> bool {
+        trace!("Executing set {} {}", immediate, register);
+        self.modify_r(register, |_, value| value | (1 << immediate));
+        false
+    }
+
+    #[inline]
+    fn execute_set_i_hl<M: Memory>(&mut self, immediate: Immediate8, memory: &mut M) -> bool {
+        trace!("Executing set {} (HL)", immediate);
+        self.modify_hl(memory, |_, value| value | (1 << immediate));
+        false
+    }
+
+    #[inline]
+    fn execute_res_i_r(&mut self, immediate: Immediate8, register: Register8) -> bool {
+        trace!("Executing res {} {}", immediate, register);
+        self.modify_r(register, |_, value| value & !(1 << immediate));
+        false
+    }
 
-        // TODO:  implement me
+    #[inline]
+    fn execute_res_i_hl<M: Memory>(&mut self, immediate: Immediate8, memory: &mut M) -> bool {
+        trace!("Executing res {} (HL)", immediate);
+        self.modify_hl(memory, |_, value| value & !(1 << immediate));
         false
     }
 
@@ -215,67 +610,219 @@ impl<B: Bios> Cpu<B> {
         false
     }
 
+    #[inline]
+    fn execute_ld_sp_hl(&mut self) -> bool {
+        trace!("Executing ld SP HL");
+        self.registers.sp = self.registers.read_register16(Register16::HL);
+        false
+    }
+
+    #[inline]
+    fn execute_ld_ii_sp<M: Memory>(&mut self, address: Address, memory: &mut M) -> bool {
+        trace!("Executing ld ({}) SP", address);
+        memory.write_double(address, self.registers.sp);
+        false
+    }
+
+    #[inline]
+    fn execute_ld_hl_sp_s(&mut self, offset: SignedImmediate8) -> bool {
+        trace!("Executing ld HL SP+{}", offset);
+        let value = self.sp_plus_offset(offset);
+        self.registers.write_register16(Register16::HL, value);
+        false
+    }
+
     #[inline]
     fn execute_and_a_r(&mut self, register: Register8) -> bool {
-        info!("Executing and a {}", register);
-        // TODO: need to set flags
-        let a_value = self.registers.read_register8(Register8::A);
-        let reg_value = self.registers.read_register8(register);
+        trace!("Executing and A {}", register);
+        let value = self.registers.read_register8(register);
+        let a = self.registers.a;
+        self.registers.a = alu::and(&mut self.registers, a, value);
+        false
+    }
 
-        self.registers
-            .write_register8(Register8::A, a_value & reg_value);
+    #[inline]
+    fn execute_and_a_i(&mut self, immediate: Immediate8) -> bool {
+        trace!("Executing and A {}", immediate);
+        let a = self.registers.a;
+        self.registers.a = alu::and(&mut self.registers, a, immediate);
+        false
+    }
+
+    #[inline]
+    fn execute_and_a_hl<M: Memory>(&mut self, memory: &M) -> bool {
+        trace!("Executing and A (HL)");
+        let value = self.read_hl(memory);
+        let a = self.registers.a;
+        self.registers.a = alu::and(&mut self.registers, a, value);
+        false
+    }
+
+    #[inline]
+    fn execute_or_a_r(&mut self, register: Register8) -> bool {
+        trace!("Executing or A {}", register);
+        let value = self.registers.read_register8(register);
+        let a = self.registers.a;
+        self.registers.a = alu::or(&mut self.registers, a, value);
+        false
+    }
+
+    #[inline]
+    fn execute_or_a_i(&mut self, immediate: Immediate8) -> bool {
+        trace!("Executing or A {}", immediate);
+        let a = self.registers.a;
+        self.registers.a = alu::or(&mut self.registers, a, immediate);
+        false
+    }
 
+    #[inline]
+    fn execute_or_a_hl<M: Memory>(&mut self, memory: &M) -> bool {
+        trace!("Executing or A (HL)");
+        let value = self.read_hl(memory);
+        let a = self.registers.a;
+        self.registers.a = alu::or(&mut self.registers, a, value);
         false
     }
 
     #[inline]
     fn execute_add_a_r(&mut self, register: Register8) -> bool {
-        info!("Executing add a {}", register);
-        // TODO: need to set flags
-        let a_value = self.registers.read_register8(Register8::A);
-        let reg_value = self.registers.read_register8(register);
+        trace!("Executing add A {}", register);
+        let value = self.registers.read_register8(register);
+        let a = self.registers.a;
+        self.registers.a = alu::add(&mut self.registers, a, value, false);
+        false
+    }
 
-        self.registers
-            .write_register8(Register8::A, a_value + reg_value);
+    #[inline]
+    fn execute_add_a_i(&mut self, immediate: Immediate8) -> bool {
+        trace!("Executing add A {}", immediate);
+        let a = self.registers.a;
+        self.registers.a = alu::add(&mut self.registers, a, immediate, false);
+        false
+    }
 
+    #[inline]
+    fn execute_add_a_hl<M: Memory>(&mut self, memory: &M) -> bool {
+        trace!("Executing add A (HL)");
+        let value = self.read_hl(memory);
+        let a = self.registers.a;
+        self.registers.a = alu::add(&mut self.registers, a, value, false);
         false
     }
 
     #[inline]
-    fn execute_xor_a_r(&mut self, register: Register8) -> bool {
-        info!("Executing xor A {}", register);
-        // TODO: need to set flags
-        use self::Register8::A;
-        let a_value = self.registers.read_register8(A);
-        let reg_value = self.registers.read_register8(register);
+    fn execute_adc_a_r(&mut self, register: Register8) -> bool {
+        trace!("Executing adc A {}", register);
+        let value = self.registers.read_register8(register);
+        let carry = self.registers.read_flag(Flag::Carry);
+        let a = self.registers.a;
+        self.registers.a = alu::add(&mut self.registers, a, value, carry);
+        false
+    }
 
-        self.registers.write_register8(A, a_value ^ reg_value);
+    #[inline]
+    fn execute_adc_a_i(&mut self, immediate: Immediate8) -> bool {
+        trace!("Executing adc A {}", immediate);
+        let carry = self.registers.read_flag(Flag::Carry);
+        let a = self.registers.a;
+        self.registers.a = alu::add(&mut self.registers, a, immediate, carry);
+        false
+    }
+
+    #[inline]
+    fn execute_adc_a_hl<M: Memory>(&mut self, memory: &M) -> bool {
+        trace!("Executing adc A (HL)");
+        let value = self.read_hl(memory);
+        let carry = self.registers.read_flag(Flag::Carry);
+        let a = self.registers.a;
+        self.registers.a = alu::add(&mut self.registers, a, value, carry);
+        false
+    }
+
+    #[inline]
+    fn execute_sub_a_r(&mut self, register: Register8) -> bool {
+        trace!("Executing sub A {}", register);
+        let value = self.registers.read_register8(register);
+        let a = self.registers.a;
+        self.registers.a = alu::sub(&mut self.registers, a, value, false);
+        false
+    }
+
+    #[inline]
+    fn execute_sub_a_i(&mut self, immediate: Immediate8) -> bool {
+        trace!("Executing sub A {}", immediate);
+        let a = self.registers.a;
+        self.registers.a = alu::sub(&mut self.registers, a, immediate, false);
+        false
+    }
+
+    #[inline]
+    fn execute_sub_a_hl<M: Memory>(&mut self, memory: &M) -> bool {
+        trace!("Executing sub A (HL)");
+        let value = self.read_hl(memory);
+        let a = self.registers.a;
+        self.registers.a = alu::sub(&mut self.registers, a, value, false);
+        false
+    }
+
+    #[inline]
+    fn execute_sbc_a_r(&mut self, register: Register8) -> bool {
+        trace!("Executing sbc A {}", register);
+        let value = self.registers.read_register8(register);
+        let carry = self.registers.read_flag(Flag::Carry);
+        let a = self.registers.a;
+        self.registers.a = alu::sub(&mut self.registers, a, value, carry);
+        false
+    }
+
+    #[inline]
+    fn execute_sbc_a_i(&mut self, immediate: Immediate8) -> bool {
+        trace!("Executing sbc A {}", immediate);
+        let carry = self.registers.read_flag(Flag::Carry);
+        let a = self.registers.a;
+        self.registers.a = alu::sub(&mut self.registers, a, immediate, carry);
+        false
+    }
 
+    #[inline]
+    fn execute_sbc_a_hl<M: Memory>(&mut self, memory: &M) -> bool {
+        trace!("Executing sbc A (HL)");
+        let value = self.read_hl(memory);
+        let carry = self.registers.read_flag(Flag::Carry);
+        let a = self.registers.a;
+        self.registers.a = alu::sub(&mut self.registers, a, value, carry);
+        false
+    }
+
+    #[inline]
+    fn execute_xor_a_r(&mut self, register: Register8) -> bool {
+        trace!("Executing xor A {}", register);
+        let value = self.registers.read_register8(register);
+        let a = self.registers.a;
+        self.registers.a = alu::xor(&mut self.registers, a, value);
         false
     }
 
     #[inline]
     fn execute_xor_a_i(&mut self, immediate: Immediate8) -> bool {
         trace!("Executing xor A {}", immediate);
-        // TODO: need to set flags
-        use self::Register8::A;
-        let a_value = self.registers.read_register8(A);
-        self.registers.write_register8(A, a_value ^ immediate);
-
+        let a = self.registers.a;
+        self.registers.a = alu::xor(&mut self.registers, a, immediate);
         false
     }
 
     #[inline]
-    fn execute_ld_io_a<M: Memory>(&mut self, immediate: Immediate8, memory: &M) -> bool {
-        trace!("Executing ldh {} A", immediate);
-        self.registers
-            .write_register8(Register8::A, memory.read(0xFF00 + Address::from(immediate)));
+    fn execute_xor_a_hl<M: Memory>(&mut self, memory: &M) -> bool {
+        trace!("Executing xor A (HL)");
+        let value = self.read_hl(memory);
+        let a = self.registers.a;
+        self.registers.a = alu::xor(&mut self.registers, a, value);
         false
     }
 
     #[inline]
-    fn execute_ld_a_io<M: Memory>(&mut self, immediate: Immediate8, memory: &mut M) -> bool {
-        trace!("TODO: notate this correctly");
+    fn execute_ld_io_a<M: Memory>(&mut self, immediate: Immediate8, memory: &mut M) -> bool {
+        trace!("Executing ldh ({}) A", immediate);
         memory.write(
             0xFF00 + Address::from(immediate),
             self.registers.read_register8(Register8::A),
@@ -283,66 +830,177 @@ impl<B: Bios> Cpu<B> {
         false
     }
 
+    #[inline]
+    fn execute_ld_a_io<M: Memory>(&mut self, immediate: Immediate8, memory: &M) -> bool {
+        trace!("Executing ldh A ({})", immediate);
+        self.registers
+            .write_register8(Register8::A, memory.read(0xFF00 + Address::from(immediate)));
+        false
+    }
+
+    #[inline]
+    fn execute_ld_ii_a<M: Memory>(&mut self, address: Address, memory: &mut M) -> bool {
+        trace!("Executing ld ({}) A", address);
+        memory.write(address, self.registers.a);
+        false
+    }
+
+    #[inline]
+    fn execute_ld_a_ii<M: Memory>(&mut self, address: Address, memory: &M) -> bool {
+        trace!("Executing ld A ({})", address);
+        self.registers.a = memory.read(address);
+        false
+    }
+
     #[inline]
     fn execute_ld_r_r(&mut self, register_a: Register8, register_b: Register8) -> bool {
         trace!("Executing ld {} {}", register_a, register_b);
-        let src = self.registers.read_register8(register_a);
-        self.registers.write_register8(register_b, src);
+        let src = self.registers.read_register8(register_b);
+        self.registers.write_register8(register_a, src);
         false
     }
 
     #[inline]
-    fn execute_ld_a_rr<M: Memory>(&mut self, register: Register16, memory: &mut M) -> bool {
+    fn execute_ld_a_rr<M: Memory>(&mut self, register: Register16, memory: &M) -> bool {
         trace!("Executing ld A ({})", register);
-        let val = self.registers.read_register8(Register8::A);
         let address = self.registers.read_register16(register);
-        memory.write(address, val);
+        self.registers.a = memory.read(address);
+        false
+    }
+
+    #[inline]
+    fn execute_ld_rr_a<M: Memory>(&mut self, register: Register16, memory: &mut M) -> bool {
+        trace!("Executing ld ({}) A", register);
+        let address = self.registers.read_register16(register);
+        memory.write(address, self.registers.a);
         false
     }
 
     #[inline]
     fn execute_call_ii<M: Memory>(&mut self, address: Address, memory: &mut M) -> bool {
         trace!("Executing call {}", address);
-        self.registers.sp -= 2;
-        let sp = self.registers.sp;
-        memory.write_double(sp, self.registers.pc);
+        let pc = self.registers.pc;
+        self.push(pc, memory);
         self.registers.pc = address;
         false
     }
 
+    #[inline]
+    fn execute_call_cond_ii<M: Memory>(
+        &mut self,
+        condition: Condition,
+        address: Address,
+        memory: &mut M,
+    ) -> bool {
+        trace!("Executing call {} {}", condition, address);
+        if self.registers.check_condition(condition) {
+            self.execute_call_ii(address, memory);
+            true
+        } else {
+            false
+        }
+    }
+
+    #[inline]
+    fn execute_ret<M: Memory>(&mut self, memory: &M) -> bool {
+        trace!("Executing ret");
+        self.registers.pc = self.pop(memory);
+        false
+    }
+
+    #[inline]
+    fn execute_ret_cond<M: Memory>(&mut self, condition: Condition, memory: &M) -> bool {
+        trace!("Executing ret {}", condition);
+        if self.registers.check_condition(condition) {
+            self.registers.pc = self.pop(memory);
+            true
+        } else {
+            false
+        }
+    }
+
+    #[inline]
+    fn execute_reti<M: Memory>(&mut self, memory: &M) -> bool {
+        trace!("Executing reti");
+        self.registers.pc = self.pop(memory);
+        self.ime = true;
+        false
+    }
+
+    #[inline]
+    fn execute_rst<M: Memory>(&mut self, vector: Immediate8, memory: &mut M) -> bool {
+        trace!("Executing rst {}", vector);
+        let pc = self.registers.pc;
+        self.push(pc, memory);
+        self.registers.pc = Address::from(vector);
+        false
+    }
+
     #[inline]
     fn execute_inc_rr(&mut self, register: Register16) -> bool {
         trace!("Executing inc {}", register);
         let val = self.registers.read_register16(register);
-        self.registers.write_register16(register, val + 1);
+        self.registers.write_register16(register, val.wrapping_add(1));
         false
     }
 
     #[inline]
     fn execute_cp_a_i(&mut self, immediate: Immediate8) -> bool {
         trace!("Executing cp A {}", immediate);
-        let a = self.registers.read_register8(Register8::A);
-        self.registers.write_flag(Flag::Zero, a == immediate);
-        self.registers.write_flag(Flag::AddSub, true);
-        self.registers.write_flag(Flag::HalfCarry, true); // TODO: this is wrong
-        self.registers.write_flag(Flag::Carry, a < immediate);
+        let a = self.registers.a;
+        alu::cp(&mut self.registers, a, immediate);
+        false
+    }
+
+    #[inline]
+    fn execute_cp_a_r(&mut self, register: Register8) -> bool {
+        trace!("Executing cp A {}", register);
+        let value = self.registers.read_register8(register);
+        let a = self.registers.a;
+        alu::cp(&mut self.registers, a, value);
+        false
+    }
+
+    #[inline]
+    fn execute_cp_a_hl<M: Memory>(&mut self, memory: &M) -> bool {
+        trace!("Executing cp A (HL)");
+        let value = self.read_hl(memory);
+        let a = self.registers.a;
+        alu::cp(&mut self.registers, a, value);
         false
     }
 
     #[inline]
     fn execute_push_rr<M: Memory>(&mut self, register: Register16, memory: &mut M) -> bool {
         trace!("Executing push {}", register);
-        self.registers.pc -= 2;
         let val = self.registers.read_register16(register);
-        memory.write_double(self.registers.pc, val);
+        self.push(val, memory);
+        false
+    }
+
+    #[inline]
+    fn execute_pop<M: Memory>(&mut self, register: Register16, memory: &M) -> bool {
+        trace!("Executing pop {}", register);
+        let mut val = self.pop(memory);
+        if register == Register16::AF {
+            // the lower nibble of the flag register is always zero
+            val &= 0xFFF0;
+        }
+        self.registers.write_register16(register, val);
         false
     }
 
     #[inline]
     fn execute_inc_r(&mut self, register: Register8) -> bool {
         trace!("Executing inc {}", register);
-        let val = self.registers.read_register8(register);
-        self.registers.write_register8(register, val + 1);
+        self.modify_r(register, |cpu, value| alu::inc(&mut cpu.registers, value));
+        false
+    }
+
+    #[inline]
+    fn execute_inc_hl<M: Memory>(&mut self, memory: &mut M) -> bool {
+        trace!("Executing inc (HL)");
+        self.modify_hl(memory, |cpu, value| alu::inc(&mut cpu.registers, value));
         false
     }
 
@@ -350,22 +1008,475 @@ impl<B: Bios> Cpu<B> {
     fn execute_dec_rr(&mut self, register: Register16) -> bool {
         trace!("Executing dec {}", register);
         let val = self.registers.read_register16(register);
-        self.registers.write_register16(register, val - 1);
+        self.registers.write_register16(register, val.wrapping_sub(1));
         false
     }
 
     #[inline]
     fn execute_dec_r(&mut self, register: Register8) -> bool {
         trace!("Executing dec {}", register);
-        let val = self.registers.read_register8(register);
-        self.registers.write_register8(register, val - 1);
+        self.modify_r(register, |cpu, value| alu::dec(&mut cpu.registers, value));
+        false
+    }
+
+    #[inline]
+    fn execute_dec_hl<M: Memory>(&mut self, memory: &mut M) -> bool {
+        trace!("Executing dec (HL)");
+        self.modify_hl(memory, |cpu, value| alu::dec(&mut cpu.registers, value));
+        false
+    }
+
+    #[inline]
+    fn execute_daa(&mut self) -> bool {
+        trace!("Executing daa");
+        let a = self.registers.a;
+        self.registers.a = alu::daa(&mut self.registers, a);
+        false
+    }
+
+    #[inline]
+    fn execute_cpl(&mut self) -> bool {
+        trace!("Executing cpl");
+        self.registers.a = !self.registers.a;
+        self.registers.write_flag(Flag::AddSub, true);
+        self.registers.write_flag(Flag::HalfCarry, true);
+        false
+    }
+
+    #[inline]
+    fn execute_add_hl_rr(&mut self, register: Register16) -> bool {
+        trace!("Executing add HL {}", register);
+        let hl = self.registers.read_register16(Register16::HL);
+        let value = self.registers.read_register16(register);
+
+        self.registers.write_flag(Flag::AddSub, false);
+        self.registers
+            .write_flag(Flag::HalfCarry, (hl & 0x0FFF) + (value & 0x0FFF) > 0x0FFF);
+        self.registers
+            .write_flag(Flag::Carry, u32::from(hl) + u32::from(value) > 0xFFFF);
+        self.registers
+            .write_register16(Register16::HL, hl.wrapping_add(value));
+        false
+    }
+
+    #[inline]
+    fn execute_add_sp_s(&mut self, offset: SignedImmediate8) -> bool {
+        trace!("Executing add SP {}", offset);
+        self.registers.sp = self.sp_plus_offset(offset);
+        false
+    }
+
+    #[inline]
+    fn execute_rlca(&mut self) -> bool {
+        trace!("Executing rlca");
+        let a = self.registers.a;
+        self.registers.a = self.rlc(a);
+        self.registers.write_flag(Flag::Zero, false);
+        false
+    }
+
+    #[inline]
+    fn execute_rla(&mut self) -> bool {
+        trace!("Executing rla");
+        let a = self.registers.a;
+        self.registers.a = self.rl(a);
+        self.registers.write_flag(Flag::Zero, false);
+        false
+    }
+
+    #[inline]
+    fn execute_rrca(&mut self) -> bool {
+        trace!("Executing rrca");
+        let a = self.registers.a;
+        self.registers.a = self.rrc(a);
+        self.registers.write_flag(Flag::Zero, false);
+        false
+    }
+
+    #[inline]
+    fn execute_rra(&mut self) -> bool {
+        trace!("Executing rra");
+        let a = self.registers.a;
+        self.registers.a = self.rr(a);
+        self.registers.write_flag(Flag::Zero, false);
+        false
+    }
+
+    #[inline]
+    fn execute_rlc_r(&mut self, register: Register8) -> bool {
+        trace!("Executing rlc {}", register);
+        self.modify_r(register, Self::rlc);
+        false
+    }
+
+    #[inline]
+    fn execute_rlc_hl<M: Memory>(&mut self, memory: &mut M) -> bool {
+        trace!("Executing rlc (HL)");
+        self.modify_hl(memory, Self::rlc);
+        false
+    }
+
+    #[inline]
+    fn execute_rl_r(&mut self, register: Register8) -> bool {
+        trace!("Executing rl {}", register);
+        self.modify_r(register, Self::rl);
+        false
+    }
+
+    #[inline]
+    fn execute_rl_hl<M: Memory>(&mut self, memory: &mut M) -> bool {
+        trace!("Executing rl (HL)");
+        self.modify_hl(memory, Self::rl);
         false
     }
+
+    #[inline]
+    fn execute_rrc_r(&mut self, register: Register8) -> bool {
+        trace!("Executing rrc {}", register);
+        self.modify_r(register, Self::rrc);
+        false
+    }
+
+    #[inline]
+    fn execute_rrc_hl<M: Memory>(&mut self, memory: &mut M) -> bool {
+        trace!("Executing rrc (HL)");
+        self.modify_hl(memory, Self::rrc);
+        false
+    }
+
+    #[inline]
+    fn execute_rr_r(&mut self, register: Register8) -> bool {
+        trace!("Executing rr {}", register);
+        self.modify_r(register, Self::rr);
+        false
+    }
+
+    #[inline]
+    fn execute_rr_hl<M: Memory>(&mut self, memory: &mut M) -> bool {
+        trace!("Executing rr (HL)");
+        self.modify_hl(memory, Self::rr);
+        false
+    }
+
+    #[inline]
+    fn execute_sla_r(&mut self, register: Register8) -> bool {
+        trace!("Executing sla {}", register);
+        self.modify_r(register, Self::sla);
+        false
+    }
+
+    #[inline]
+    fn execute_sla_hl<M: Memory>(&mut self, memory: &mut M) -> bool {
+        trace!("Executing sla (HL)");
+        self.modify_hl(memory, Self::sla);
+        false
+    }
+
+    #[inline]
+    fn execute_swap_r(&mut self, register: Register8) -> bool {
+        trace!("Executing swap {}", register);
+        self.modify_r(register, Self::swap);
+        false
+    }
+
+    #[inline]
+    fn execute_swap_hl<M: Memory>(&mut self, memory: &mut M) -> bool {
+        trace!("Executing swap (HL)");
+        self.modify_hl(memory, Self::swap);
+        false
+    }
+
+    #[inline]
+    fn execute_sra_r(&mut self, register: Register8) -> bool {
+        trace!("Executing sra {}", register);
+        self.modify_r(register, Self::sra);
+        false
+    }
+
+    #[inline]
+    fn execute_sra_hl<M: Memory>(&mut self, memory: &mut M) -> bool {
+        trace!("Executing sra (HL)");
+        self.modify_hl(memory, Self::sra);
+        false
+    }
+
+    #[inline]
+    fn execute_srl_r(&mut self, register: Register8) -> bool {
+        trace!("Executing srl {}", register);
+        self.modify_r(register, Self::srl);
+        false
+    }
+
+    #[inline]
+    fn execute_srl_hl<M: Memory>(&mut self, memory: &mut M) -> bool {
+        trace!("Executing srl (HL)");
+        self.modify_hl(memory, Self::srl);
+        false
+    }
+
+    #[inline]
+    fn execute_ccf(&mut self) -> bool {
+        trace!("Executing ccf");
+        let carry = self.registers.read_flag(Flag::Carry);
+        self.registers.write_flag(Flag::AddSub, false);
+        self.registers.write_flag(Flag::HalfCarry, false);
+        self.registers.write_flag(Flag::Carry, !carry);
+        false
+    }
+
+    #[inline]
+    fn execute_scf(&mut self) -> bool {
+        trace!("Executing scf");
+        self.registers.write_flag(Flag::AddSub, false);
+        self.registers.write_flag(Flag::HalfCarry, false);
+        self.registers.write_flag(Flag::Carry, true);
+        false
+    }
+
+    #[inline]
+    fn execute_halt<M: Memory>(&mut self, memory: &M) -> bool {
+        trace!("Executing halt");
+        let requested = interrupt::from_register(memory.read(INTERRUPT_FLAG));
+        let pending = requested & Self::enabled_interrupts(memory);
+
+        if !self.ime && !pending.is_empty() {
+            // the DMG doesn't halt here, and fails to increment PC on the next fetch instead
+            debug!("Triggered halt bug");
+            self.halt_bug = true;
+        } else {
+            self.is_halted = true;
+        }
+        false
+    }
+
+    #[inline]
+    fn execute_stop(&mut self) -> bool {
+        trace!("Executing stop");
+        self.is_stopped = true;
+        false
+    }
+
+    #[inline]
+    fn execute_undefined(&mut self, opcode: Word) -> bool {
+        // undefined opcodes lock up the CPU, so keep executing the same instruction forever
+        warn!("Executing undefined opcode {:#04x}", opcode);
+        self.registers.pc = self.registers.pc.wrapping_sub(1);
+        false
+    }
+}
+
+/// A read-only view of memory as the CPU decodes it while the HALT bug is in effect
+struct HaltBugView<'a, M: 'a> {
+    memory: &'a M,
+    pc: Address,
+}
+
+impl<'a, M: Memory> Memory for HaltBugView<'a, M> {
+    fn read(&self, address: Address) -> Word {
+        if address == self.pc {
+            self.memory.read(address)
+        } else {
+            self.memory.read(address.wrapping_sub(1))
+        }
+    }
+
+    fn write(&mut self, _address: Address, _value: Word) {
+        unreachable!("instructions are decoded without writing to memory")
+    }
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl<B: Bios + fmt::Debug> fmt::Debug for Cpu<B> {
+impl fmt::Debug for Cpu {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         // TODO: implement Cpu::debug
         unimplemented!()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A flat 64KB address space for running small programs
+    struct FlatMemory(Vec<Word>);
+
+    impl FlatMemory {
+        fn with_program(program: &[Word]) -> Self {
+            let mut memory = vec![0; 0x10000];
+            memory[..program.len()].copy_from_slice(program);
+            FlatMemory(memory)
+        }
+    }
+
+    impl Memory for FlatMemory {
+        fn read(&self, address: Address) -> Word {
+            self.0[address as usize]
+        }
+
+        fn write(&mut self, address: Address, value: Word) {
+            self.0[address as usize] = value;
+        }
+    }
+
+    fn run(program: &[Word], steps: usize) -> (Cpu, FlatMemory) {
+        let mut cpu = Cpu::new();
+        let mut memory = FlatMemory::with_program(program);
+        for _ in 0..steps {
+            cpu.step(&mut memory);
+        }
+        (cpu, memory)
+    }
+
+    #[test]
+    fn jr_nz_loops_until_zero() {
+        // ld a, 3; sub 1; jr nz, -4; ld b, 0x42
+        let program = [0x3E, 0x03, 0xD6, 0x01, 0x20, 0xFC, 0x06, 0x42];
+        let (cpu, _) = run(&program, 1 + 3 * 2 + 1);
+        assert_eq!(cpu.registers().a, 0);
+        assert_eq!(cpu.registers().b, 0x42);
+    }
+
+    #[test]
+    fn call_and_ret_use_the_stack() {
+        // ld sp, 0xFFFE; call 0x0010; nop ... 0x0010: ret
+        let mut program = vec![0x31, 0xFE, 0xFF, 0xCD, 0x10, 0x00];
+        program.resize(0x11, 0x00);
+        program[0x10] = 0xC9;
+
+        let (cpu, memory) = run(&program, 2);
+        assert_eq!(cpu.registers().pc, 0x0010);
+        assert_eq!(cpu.registers().sp, 0xFFFC);
+        assert_eq!(memory.read_double(0xFFFC), 0x0006);
+
+        let (cpu, _) = run(&program, 3);
+        assert_eq!(cpu.registers().pc, 0x0006);
+        assert_eq!(cpu.registers().sp, 0xFFFE);
+    }
+
+    #[test]
+    fn pop_af_clears_low_flag_nibble() {
+        // ld sp, 0xFFFE; ld bc, 0x12FF; push bc; pop af
+        let program = [0x31, 0xFE, 0xFF, 0x01, 0xFF, 0x12, 0xC5, 0xF1];
+        let (cpu, _) = run(&program, 4);
+        assert_eq!(cpu.registers().read_register16(Register16::AF), 0x12F0);
+    }
+
+    #[test]
+    fn cb_prefixed_instructions_execute() {
+        // ld a, 0x81; rlc a; set 6, a; res 0, a; bit 7, a
+        let program = [0x3E, 0x81, 0xCB, 0x07, 0xCB, 0xF7, 0xCB, 0x87, 0xCB, 0x7F];
+        let (cpu, _) = run(&program, 5);
+        assert_eq!(cpu.registers().a, 0x42);
+        assert!(cpu.registers().read_flag(Flag::Zero));
+        assert!(cpu.registers().read_flag(Flag::Carry));
+    }
+
+    #[test]
+    fn interrupts_dispatch_one_instruction_after_ei() {
+        // ld sp, 0xFFFE; ei; nop; nop
+        let mut program = vec![0x31, 0xFE, 0xFF, 0xFB, 0x00, 0x00];
+        program.resize(0x51, 0x00);
+        program[0x50] = 0xD9; // reti at the timer vector
+
+        let mut cpu = Cpu::new();
+        let mut memory = FlatMemory::with_program(&program);
+        memory.write(INTERRUPT_ENABLE, 0x04);
+        memory.write(INTERRUPT_FLAG, 0x06);
+
+        // ld sp; ei
+        cpu.step(&mut memory);
+        cpu.step(&mut memory);
+        assert_eq!(cpu.service_interrupts(&mut memory), 0);
+
+        // the instruction after ei still runs before the interrupt is taken
+        cpu.step(&mut memory);
+        assert_eq!(cpu.service_interrupts(&mut memory), DISPATCH_CYCLES);
+        assert_eq!(cpu.registers().pc, 0x0050);
+        assert_eq!(memory.read(INTERRUPT_FLAG), 0x02);
+        assert_eq!(memory.read_double(0xFFFC), 0x0005);
+        assert!(!cpu.ime());
+
+        // reti re-enables interrupts immediately
+        cpu.step(&mut memory);
+        assert_eq!(cpu.registers().pc, 0x0005);
+        assert!(cpu.ime());
+    }
+
+    #[test]
+    fn pending_interrupt_wakes_halt_without_ime() {
+        // halt; ld a, 0x42
+        let program = [0x76, 0x3E, 0x42];
+        let mut cpu = Cpu::new();
+        let mut memory = FlatMemory::with_program(&program);
+        memory.write(INTERRUPT_ENABLE, 0x01);
+
+        cpu.step(&mut memory);
+        cpu.step(&mut memory);
+        assert!(cpu.is_halted());
+
+        memory.write(INTERRUPT_FLAG, 0x01);
+        assert_eq!(cpu.service_interrupts(&mut memory), 0);
+        assert!(!cpu.is_halted());
+        cpu.step(&mut memory);
+        assert_eq!(cpu.registers().a, 0x42);
+    }
+
+    #[test]
+    fn halt_bug_reads_next_byte_twice() {
+        // halt; inc a; ld b, a
+        let program = [0x76, 0x3C, 0x47];
+        let mut cpu = Cpu::new();
+        let mut memory = FlatMemory::with_program(&program);
+        memory.write(INTERRUPT_ENABLE, 0x01);
+        memory.write(INTERRUPT_FLAG, 0x01);
+
+        for _ in 0..4 {
+            cpu.step(&mut memory);
+        }
+        assert!(!cpu.is_halted());
+        assert_eq!(cpu.registers().a, 2);
+        assert_eq!(cpu.registers().b, 2);
+    }
+
+    #[test]
+    fn halt_bug_reuses_opcode_as_operand() {
+        // halt; ld a, 0x14
+        let program = [0x76, 0x3E, 0x14];
+        let mut cpu = Cpu::new();
+        let mut memory = FlatMemory::with_program(&program);
+        memory.write(INTERRUPT_ENABLE, 0x04);
+        memory.write(INTERRUPT_FLAG, 0x04);
+
+        cpu.step(&mut memory);
+        cpu.step(&mut memory);
+        assert_eq!(cpu.registers().a, 0x3E);
+        assert_eq!(cpu.registers().pc, 0x0002);
+    }
+
+    #[test]
+    fn stop_idles_until_resumed() {
+        // stop; ld a, 0x42
+        let program = [0x10, 0x00, 0x3E, 0x42];
+        let (mut cpu, mut memory) = run(&program, 2);
+        assert!(cpu.is_stopped());
+        assert_eq!(cpu.registers().a, 0);
+
+        cpu.resume();
+        cpu.step(&mut memory);
+        assert_eq!(cpu.registers().a, 0x42);
+    }
+
+    #[test]
+    fn rst_jumps_to_vector() {
+        // ld sp, 0xFFFE; rst 0x28
+        let program = [0x31, 0xFE, 0xFF, 0xEF];
+        let (cpu, memory) = run(&program, 2);
+        assert_eq!(cpu.registers().pc, 0x0028);
+        assert_eq!(memory.read_double(0xFFFC), 0x0004);
+    }
+}
@@ -11,7 +11,7 @@
 use std::fmt;
 
 use hardware::{pack_words, split_doubleword};
-use isa::{DoubleWord, Flag, Register16, Register8, Word};
+use isa::{Condition, DoubleWord, Flag, Register16, Register8, Word};
 
 const ZF_FLAG_BIT_N: u8 = 7;
 const NF_FLAG_BIT_N: u8 = 6;
@@ -140,6 +140,17 @@ impl Registers {
         val
     }
 
+    /// Return true if a branch condition holds for the current flags
+    pub fn check_condition(&self, condition: Condition) -> bool {
+        use self::Condition::*;
+        match condition {
+            NotZero => !self.read_flag(Flag::Zero),
+            Zero => self.read_flag(Flag::Zero),
+            NotCarry => !self.read_flag(Flag::Carry),
+            Carry => self.read_flag(Flag::Carry),
+        }
+    }
+
     /// Write the value of a flag
     pub fn write_flag(&mut self, flag: Flag, value: bool) {
         use self::Flag::*;
@@ -0,0 +1,91 @@
+// Copyright 2018 Will Johnston
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Interrupt sources and `IE`/`IF` register encoding
+
+use enumset::EnumSet;
+use isa::{Address, Word};
+
+/// Number of cycles it takes to dispatch an interrupt to its vector
+pub const DISPATCH_CYCLES: u8 = 20;
+
+/// Bits of the `IE` and `IF` registers that hold interrupt flags
+const INTERRUPT_BITS_MASK: Word = 0x1F;
+
+/// A set of interrupts
+pub type Interrupts = EnumSet<Interrupt>;
+
+enum_set_type! {
+    /// An interrupt source
+    ///
+    /// Variants are declared in priority order, highest first, and their position matches
+    /// their bit in the `IE` and `IF` registers
+    pub enum Interrupt {
+        VBlank,
+        LcdStat,
+        Timer,
+        Serial,
+        Joypad,
+    }
+}
+
+impl Interrupt {
+    /// Return the address the CPU jumps to when servicing the interrupt
+    pub fn vector(self) -> Address {
+        use self::Interrupt::*;
+        match self {
+            VBlank => 0x0040,
+            LcdStat => 0x0048,
+            Timer => 0x0050,
+            Serial => 0x0058,
+            Joypad => 0x0060,
+        }
+    }
+}
+
+/// Decode the value of an `IE` or `IF` register into a set of interrupts
+pub fn from_register(value: Word) -> Interrupts {
+    Interrupts::from_bits(u128::from(value & INTERRUPT_BITS_MASK))
+}
+
+/// Encode a set of interrupts into the value of an `IE` or `IF` register
+pub fn to_register(interrupts: Interrupts) -> Word {
+    interrupts.to_bits() as Word
+}
+
+/// Return the interrupt with the highest priority in a set, if there is one
+pub fn highest_priority(interrupts: Interrupts) -> Option<Interrupt> {
+    interrupts.iter().next()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn register_bits_match_sources() {
+        assert_eq!(to_register(Interrupt::VBlank.into()), 0x01);
+        assert_eq!(to_register(Interrupt::LcdStat.into()), 0x02);
+        assert_eq!(to_register(Interrupt::Timer.into()), 0x04);
+        assert_eq!(to_register(Interrupt::Serial.into()), 0x08);
+        assert_eq!(to_register(Interrupt::Joypad.into()), 0x10);
+    }
+
+    #[test]
+    fn highest_priority_is_lowest_bit() {
+        let pending = from_register(0b1_1100);
+        assert_eq!(highest_priority(pending), Some(Interrupt::Timer));
+        assert_eq!(highest_priority(from_register(0xE0)), None);
+    }
+
+    quickcheck! {
+        fn register_round_trips_lower_five_bits(value: Word) -> bool {
+            to_register(from_register(value)) == value & INTERRUPT_BITS_MASK
+        }
+    }
+}
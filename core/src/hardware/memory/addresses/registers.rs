@@ -13,6 +13,9 @@ use isa::Address;
 // Registers addresses
 pub const INTERRUPT_ENABLE: Address = 0xFFFF;
 
+/// Interrupt flag register address
+pub const INTERRUPT_FLAG: Address = 0xFF0F;
+
 /// LCD Control register addres
 pub const LCDC: Address = 0xFF40;
 
@@ -25,8 +28,11 @@ pub const SCY: Address = 0xFF42;
 /// Scroll position X
 pub const SCX: Address = 0xFF43;
 
+/// Current scanline
+pub const LY: Address = 0xFF44;
+
 /// LY Compare reigster
-pub const LYC: Address = 0x0000;
+pub const LYC: Address = 0xFF45;
 
 /// Window Y position
 pub const WY: Address = 0xFF4A;
@@ -37,6 +43,12 @@ pub const WX: Address = 0xFF4b;
 /// BG Pallete data
 pub const BGP: Address = 0xFF47;
 
+/// Sprite pallete 0 data
+pub const OBP0: Address = 0xFF48;
+
+/// Sprite pallete 1 data
+pub const OBP1: Address = 0xFF49;
+
 /// Background pallete index
 pub const BCPS: Address = 0xFF68;
 
@@ -70,4 +82,11 @@ pub const HDMA4: Address = 0xFF52;
 /// New dma length or mode or start
 pub const HDMA5: Address = 0xFF52;
 
-pub const JOYP: Address = 0xFF0;
+/// Joypad register
+pub const JOYP: Address = 0xFF00;
+
+/// Boot ROM disable register
+pub const BOOT: Address = 0xFF50;
+
+/// CGB speed switch register
+pub const KEY1: Address = 0xFF4D;
@@ -37,13 +37,13 @@ pub trait Memory {
     fn write_double(&mut self, address: Address, value: DoubleWord) {
         let (lo, hi) = split_doubleword(value);
         self.write(address, lo);
-        self.write(address, hi);
+        self.write(address.wrapping_add(1), hi);
     }
 
     /// Read a `DoubleWord`
     fn read_double(&self, address: Address) -> DoubleWord {
         let lo = self.read(address);
-        let hi = self.read(address + 1);
+        let hi = self.read(address.wrapping_add(1));
 
         pack_words(lo, hi)
     }
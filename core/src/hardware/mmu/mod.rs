@@ -10,16 +10,36 @@ pub mod swram;
 pub use self::swram::Swram;
 
 use std::mem::replace;
-use system::Buttons;
+use system::{Button, Buttons, Model, Tilt};
 
+use hardware::bios::Bios;
+use hardware::cartridge::camera::ImagePort;
+use hardware::cartridge::header::NINTENDO_LOGO;
+use hardware::cartridge::infrared::InfraredPort;
+use hardware::cartridge::speaker::SpeakerPort;
+use hardware::interrupt::{self, Interrupt};
 use hardware::memory::addresses::memory_map::*;
+use hardware::memory::addresses::registers::{BOOT, INTERRUPT_FLAG, JOYP, KEY1, LCDS, LY};
 use hardware::memory::Memory;
 use hardware::memory::{Memory4Kb, Memory8Kb};
 use hardware::Cartridge;
 use isa::{Address, Word};
 
+/// First tile the boot rom draws the logo into
+const LOGO_TILES_OFFSET: Address = 0x8010;
+
+/// Tile map entry of the top left logo tile
+const LOGO_MAP_TOP: Address = 0x9904;
+
+/// Tile map entry of the bottom left logo tile
+const LOGO_MAP_BOTTOM: Address = 0x9924;
+
+/// The registered trademark symbol drawn after the logo
+const TRADEMARK_TILE: [Word; 8] = [0x3C, 0x42, 0xB9, 0xA5, 0xB9, 0xA5, 0x42, 0x3C];
+
 /// A Gameboy Memory management unit
-pub struct Mmu<S: Swram> {
+pub struct Mmu<S: Swram, B: Bios> {
+    bios: Option<B>, // mapped over the cartridge until boot finishes
     cartridge: Option<Cartridge>,
     vram: Memory8Kb,         // video ram
     wram: Memory4Kb,         // work ram
@@ -27,9 +47,62 @@ pub struct Mmu<S: Swram> {
     oam: [Word; OAM_SIZE],   // Object attribute map
     iom: [Word; IOM_SIZE],   // IO memory
     hram: [Word; HRAM_SIZE], // high ram
+    input: Buttons,
+    double_speed: bool,
 }
 
-impl<S: Swram> Mmu<S> {
+impl<S: Swram + Default, B: Bios> Mmu<S, B> {
+    /// Create a new MMU with a bios mapped over the cartridge
+    pub fn with_bios(bios: B) -> Self {
+        Self {
+            bios: Some(bios),
+            ..Self::default()
+        }
+    }
+
+    /// Create a new MMU in the state the boot rom of `model` leaves it in when it hands
+    /// control to the cartridge
+    pub fn post_boot(model: Model) -> Self {
+        let mut mmu = Self::default();
+        let dmg_or_cgb = |dmg, cgb| if model.is_cgb() { cgb } else { dmg };
+
+        // DIV keeps counting through the boot rom, so it depends on how long the boot rom runs
+        let div = match model {
+            Model::Dmg0 => 0x18,
+            Model::Dmg | Model::Mgb => 0xAB,
+            Model::Sgb | Model::Cgb | Model::Agb => 0x00,
+        };
+        let nr52 = if model == Model::Sgb { 0xF0 } else { 0xF1 };
+        let stat = if model == Model::Dmg0 { 0x81 } else { 0x85 };
+
+        // registers that differ from their power on values once the boot rom has run. The
+        // vblank interrupt raised while the logo was shown is still pending in IF
+        let io = [
+            (0xFF02, dmg_or_cgb(0x7E, 0x7F)), // SC
+            (0xFF04, div),                    // DIV
+            (0xFF0F, 0xE1),                   // IF
+            (0xFF13, 0xFF),                   // NR13
+            (0xFF18, 0xFF),                   // NR23
+            (0xFF1D, 0xFF),                   // NR33
+            (0xFF26, nr52),                   // NR52
+            (0xFF41, stat),                   // STAT
+            (0xFF46, dmg_or_cgb(0xFF, 0x00)), // DMA
+        ];
+        for &(address, value) in io.iter() {
+            mmu.iom[usize::from(address - IOM_OFFSET)] = value;
+        }
+
+        mmu.load_logo();
+        mmu
+    }
+}
+
+impl<S: Swram, B: Bios> Mmu<S, B> {
+    /// Returns true if the bios is still mapped over the cartridge
+    pub fn is_bios_mapped(&self) -> bool {
+        self.bios.is_some()
+    }
+
     pub fn vram(&self) -> &Memory8Kb {
         &self.vram
     }
@@ -44,8 +117,159 @@ impl<S: Swram> Mmu<S> {
         self.cartridge.take()
     }
 
+    /// Return the loaded cartridge, if there is one
+    pub fn cartridge(&self) -> Option<&Cartridge> {
+        self.cartridge.as_ref()
+    }
+
+    /// Return the loaded cartridge mutably, if there is one
+    pub fn cartridge_mut(&mut self) -> Option<&mut Cartridge> {
+        self.cartridge.as_mut()
+    }
+
+    /// Emulate the hardware on the loaded cartridge over a given number of cycles
+    pub fn emulate_cartridge(&mut self, cycles: usize) {
+        if let Some(ref mut cartridge) = self.cartridge {
+            cartridge.emulate(cycles)
+        }
+    }
+
+    /// Connect an infrared transceiver to the loaded cartridge
+    pub fn connect_infrared(&mut self, port: InfraredPort) {
+        if let Some(ref mut cartridge) = self.cartridge {
+            cartridge.connect_infrared(port)
+        }
+    }
+
+    /// Connect a tone generator to the loaded cartridge
+    pub fn connect_speaker(&mut self, port: SpeakerPort) {
+        if let Some(ref mut cartridge) = self.cartridge {
+            cartridge.connect_speaker(port)
+        }
+    }
+
+    /// Connect an image provider to the loaded cartridge
+    pub fn connect_camera(&mut self, port: ImagePort) {
+        if let Some(ref mut cartridge) = self.cartridge {
+            cartridge.connect_camera(port)
+        }
+    }
+
+    /// Update the tilt the loaded cartridge's accelerometer reads from
+    pub fn update_tilt(&mut self, tilt: Tilt) {
+        if let Some(ref mut cartridge) = self.cartridge {
+            cartridge.set_tilt(tilt)
+        }
+    }
+
+    /// Returns true if the loaded cartridge's rumble motor is running
+    pub fn is_rumbling(&self) -> bool {
+        self.cartridge
+            .as_ref()
+            .map_or(false, Cartridge::is_rumbling)
+    }
+
+    /// Request an interrupt by raising its bit in the `IF` register
+    pub fn request_interrupt(&mut self, interrupt: Interrupt) {
+        trace!("Requesting {:?} interrupt", interrupt);
+        let requested = interrupt::from_register(self.read(INTERRUPT_FLAG)) | interrupt;
+        self.write(INTERRUPT_FLAG, interrupt::to_register(requested));
+    }
+
+    /// Update the buttons seen through `JOYP`, requesting a joypad interrupt if a selected
+    /// input line goes low
     pub fn update_input_registers(&mut self, input: Buttons) {
-        debug!("input not yet implemented")
+        let before = self.read(JOYP);
+        self.input = input;
+        let after = self.read(JOYP);
+
+        if before & !after & 0x0F != 0 {
+            self.request_interrupt(Interrupt::Joypad);
+        }
+    }
+
+    /// Update the registers the PPU drives: `LY`, and the mode and coincidence flag in the low
+    /// three bits of `STAT`
+    pub fn update_lcd_registers(&mut self, ly: Word, status: Word) {
+        self.iom[(LY - IOM_OFFSET) as usize] = ly;
+        let stat = &mut self.iom[(LCDS - IOM_OFFSET) as usize];
+        *stat = (*stat & 0x78) | (status & 0x07);
+    }
+
+    /// Returns true if the CPU is running in CGB double speed mode
+    pub fn is_double_speed(&self) -> bool {
+        self.double_speed
+    }
+
+    /// Switch CPU speed if a switch was prepared through `KEY1`. Returns true if the speed
+    /// changed
+    pub fn try_switch_speed(&mut self) -> bool {
+        let key1 = &mut self.iom[(KEY1 - IOM_OFFSET) as usize];
+        if *key1 & 0x01 == 0 {
+            return false;
+        }
+
+        *key1 = 0x00;
+        self.double_speed = !self.double_speed;
+        debug!("Switched to double speed: {}", self.double_speed);
+        true
+    }
+
+    /// Return the value of `KEY1`, with the current speed in the top bit
+    fn read_speed_switch(&self) -> Word {
+        let prepared = self.iom[(KEY1 - IOM_OFFSET) as usize] & 0x01;
+        0x7E | ((self.double_speed as Word) << 7) | prepared
+    }
+
+    /// Return the value of `JOYP` for the currently selected button group
+    fn read_joypad(&self) -> Word {
+        let select = self.iom[(JOYP - IOM_OFFSET) as usize] & 0x30;
+        let line = |button, bit: Word| if self.input.contains(button) { 1 << bit } else { 0 };
+        let mut pressed = 0;
+
+        // a low select bit picks the group, and pressed buttons pull their line low
+        if select & 0x10 == 0 {
+            pressed |= line(Button::Right, 0)
+                | line(Button::Left, 1)
+                | line(Button::Up, 2)
+                | line(Button::Down, 3);
+        }
+
+        if select & 0x20 == 0 {
+            pressed |= line(Button::A, 0)
+                | line(Button::B, 1)
+                | line(Button::Select, 2)
+                | line(Button::Start, 3);
+        }
+
+        0xC0 | select | (!pressed & 0x0F)
+    }
+
+    /// Draw the nintendo logo into video ram the same way the boot rom does
+    fn load_logo(&mut self) {
+        // every nibble of the logo becomes one row, drawn twice to double the logo's height
+        let mut address = LOGO_TILES_OFFSET - VRAM_OFFSET;
+        for &byte in NINTENDO_LOGO.iter() {
+            for &nibble in [byte >> 4, byte & 0x0F].iter() {
+                let row = double_logo_pixels(nibble);
+                self.vram.write(address, row);
+                self.vram.write(address + 2, row);
+                address += 4;
+            }
+        }
+
+        // the registered trademark symbol follows the logo, stored in the boot rom itself
+        for &row in TRADEMARK_TILE.iter() {
+            self.vram.write(address, row);
+            address += 2;
+        }
+
+        // two rows of twelve tiles, with the trademark symbol at the end of the top row
+        for i in 0..12 {
+            self.vram.write(LOGO_MAP_TOP + i - VRAM_OFFSET, i as Word + 0x01);
+            self.vram.write(LOGO_MAP_BOTTOM + i - VRAM_OFFSET, i as Word + 0x0D);
+        }
+        self.vram.write(LOGO_MAP_TOP + 12 - VRAM_OFFSET, 0x19);
     }
 
     /// Create a new IO memory section for the gameboy
@@ -89,38 +313,45 @@ impl<S: Swram> Mmu<S> {
     }
 }
 
-impl<S: Swram> Memory for Mmu<S> {
+/// Stretch the four pixels of a logo row to eight by doubling every bit
+fn double_logo_pixels(nibble: Word) -> Word {
+    (0..4).fold(0, |row, bit| row | ((nibble >> bit) & 1) * 0b11 << (bit * 2))
+}
+
+impl<S: Swram, B: Bios> Memory for Mmu<S, B> {
     /// Read a word from memory
     fn read(&self, address: Address) -> Word {
+        if let Some(ref bios) = self.bios {
+            if bios.maps(address) {
+                return bios.read(address);
+            }
+        }
+
         let val = match address {
-            ROM0_OFFSET...ROM0_END => if let Some(ref cartridge) = self.cartridge {
-                cartridge.read(address - ROM0_OFFSET)
-            } else {
-                Word::default()
-            },
-            SROM_OFFSET...SROM_END => if let Some(ref cartridge) = self.cartridge {
-                cartridge.read(address - SROM_END)
-            } else {
-                Word::default()
-            },
-            ERAM_OFFSET...ERAM_END => if let Some(ref cartridge) = self.cartridge {
-                cartridge.read(address - ERAM_OFFSET)
-            } else {
-                Word::default()
-            },
+            // the cartridge decodes its own address space, so addresses are passed unchanged
+            ROM0_OFFSET...SROM_END | ERAM_OFFSET...ERAM_END => {
+                if let Some(ref cartridge) = self.cartridge {
+                    cartridge.read(address)
+                } else {
+                    Word::default()
+                }
+            }
             VRAM_OFFSET...VRAM_END => self.vram.read(address - VRAM_OFFSET),
             WRAM_OFFSET...WRAM_END => self.wram.read(address - WRAM_OFFSET),
             SWRAM_OFFSET...SWRAM_END => self.swram.read(address - SWRAM_OFFSET),
-            ECHO_RAM_OFFSET...ECHO_RAM_END => if let Some(ref cartridge) = self.cartridge {
-                cartridge.read(address - ECHO_RAM_OFFSET)
-            } else {
-                Word::default()
-            },
+            // echo ram mirrors work ram
+            ECHO_RAM_OFFSET...ECHO_RAM_END => self.read(address - ECHO_RAM_OFFSET + WRAM_OFFSET),
             OAM_OFFSET...OAM_END => self.oam[(address - OAM_OFFSET) as usize],
             address @ UNUSABLE_MEMORY_OFFSET...UNUSABLE_MEMORY_END => {
                 warn!("Tried to read from unusable memory at address {}", address);
                 Word::default()
             }
+            // the unused upper bits of IF always read as set
+            INTERRUPT_FLAG => self.iom[(address - IOM_OFFSET) as usize] | 0xE0,
+            JOYP => self.read_joypad(),
+            // the top bit of STAT is unused and always reads as set
+            LCDS => self.iom[(address - IOM_OFFSET) as usize] | 0x80,
+            KEY1 => self.read_speed_switch(),
             IOM_OFFSET...IOM_END => self.iom[(address - IOM_OFFSET) as usize],
             HRAM_OFFSET...HRAM_END => self.hram[(address - HRAM_OFFSET) as usize],
             _ => unreachable!(),
@@ -134,25 +365,32 @@ impl<S: Swram> Memory for Mmu<S> {
     fn write(&mut self, address: Address, value: Word) {
         trace!("Wrote value of {:?} to address {:?}", value, address);
         match address {
-            ROM0_OFFSET...ROM0_END => if let Some(ref mut cartridge) = self.cartridge {
-                cartridge.write(address - ROM0_OFFSET, value)
-            },
-            SROM_OFFSET...SROM_END => if let Some(ref mut cartridge) = self.cartridge {
-                cartridge.write(address - SROM_END, value)
-            },
-            ERAM_OFFSET...ERAM_END => if let Some(ref mut cartridge) = self.cartridge {
-                cartridge.write(address - ERAM_OFFSET, value)
-            },
+            ROM0_OFFSET...SROM_END | ERAM_OFFSET...ERAM_END => {
+                if let Some(ref mut cartridge) = self.cartridge {
+                    cartridge.write(address, value)
+                }
+            }
             VRAM_OFFSET...VRAM_END => self.vram.write(address - VRAM_OFFSET, value),
             WRAM_OFFSET...WRAM_END => self.wram.write(address - WRAM_OFFSET, value),
             SWRAM_OFFSET...SWRAM_END => self.swram.write(address - SWRAM_OFFSET, value),
-            ECHO_RAM_OFFSET...ECHO_RAM_END => if let Some(ref mut cartridge) = self.cartridge {
-                cartridge.write(address - ECHO_RAM_OFFSET, value)
-            },
+            ECHO_RAM_OFFSET...ECHO_RAM_END => {
+                self.write(address - ECHO_RAM_OFFSET + WRAM_OFFSET, value)
+            }
             OAM_OFFSET...OAM_END => self.oam[(address - OAM_OFFSET) as usize] = value,
             address @ UNUSABLE_MEMORY_OFFSET...UNUSABLE_MEMORY_END => {
                 warn!("Tried to write to unusable memory at address {}", address)
             }
+            JOYP => self.iom[(address - IOM_OFFSET) as usize] = value & 0x30,
+            BOOT => if value != 0 && self.bios.take().is_some() {
+                debug!("Unmapped bios");
+            },
+            KEY1 => self.iom[(address - IOM_OFFSET) as usize] = value & 0x01,
+            // LY and the status bits of STAT are driven by the PPU
+            LY => {}
+            LCDS => {
+                let stat = &mut self.iom[(address - IOM_OFFSET) as usize];
+                *stat = (value & 0x78) | (*stat & 0x07);
+            }
             IOM_OFFSET...IOM_END => self.iom[(address - IOM_OFFSET) as usize] = value,
             HRAM_OFFSET...HRAM_END => self.hram[(address - HRAM_OFFSET) as usize] = value,
             _ => unreachable!(),
@@ -160,13 +398,14 @@ impl<S: Swram> Memory for Mmu<S> {
     }
 }
 
-impl<S: Swram + Default> Default for Mmu<S> {
+impl<S: Swram + Default, B: Bios> Default for Mmu<S, B> {
     fn default() -> Self {
         let oam = [0; OAM_SIZE];
         let iom = Self::new_io_memory();
         let hram = [0; HRAM_SIZE];
 
         Self {
+            bios: None,
             cartridge: None,
             vram: Memory8Kb::default(),
             wram: Memory4Kb::default(),
@@ -174,9 +413,104 @@ impl<S: Swram + Default> Default for Mmu<S> {
             oam,
             iom,
             hram,
+            input: Buttons::empty(),
+            double_speed: false,
         }
     }
 }
 
 #[cfg(test)]
-mod test {}
+mod test {
+    use super::*;
+    use hardware::bios::{CgbBios, GbBios};
+    use hardware::mmu::swram::Fixed;
+
+    type TestMmu = Mmu<Fixed, GbBios>;
+
+    #[test]
+    fn bios_is_mapped_until_disabled() {
+        let mut mmu = TestMmu::with_bios(GbBios::from([0x31; 0x100]));
+        assert_eq!(mmu.read(0x0000), 0x31);
+        assert_eq!(mmu.read(0x00FF), 0x31);
+        assert_eq!(mmu.read(0x0100), 0x00);
+
+        mmu.write(BOOT, 0x01);
+        assert!(!mmu.is_bios_mapped());
+        assert_eq!(mmu.read(0x0000), 0x00);
+    }
+
+    #[test]
+    fn cgb_bios_leaves_header_visible() {
+        let mmu = Mmu::<Fixed, CgbBios>::with_bios(CgbBios::from([0x31; 0x900]));
+        assert_eq!(mmu.read(0x00FF), 0x31);
+        assert_eq!(mmu.read(0x0150), 0x00);
+        assert_eq!(mmu.read(0x0200), 0x31);
+        assert_eq!(mmu.read(0x08FF), 0x31);
+        assert_eq!(mmu.read(0x0900), 0x00);
+    }
+
+    #[test]
+    fn post_boot_draws_logo() {
+        let mmu = TestMmu::post_boot(Model::Dmg);
+        assert_eq!(mmu.read(0x8010), 0xF0);
+        assert_eq!(mmu.read(0x8011), 0x00);
+        assert_eq!(mmu.read(0x8012), 0xF0);
+        assert_eq!(mmu.read(0x8014), 0xFC);
+        assert_eq!(mmu.read(0x8190), 0x3C);
+        assert_eq!(mmu.read(0x9904), 0x01);
+        assert_eq!(mmu.read(0x9910), 0x19);
+        assert_eq!(mmu.read(0x992F), 0x18);
+        assert_eq!(mmu.read(INTERRUPT_FLAG), 0xE1);
+    }
+
+    #[test]
+    fn echo_ram_mirrors_work_ram() {
+        let mut mmu = TestMmu::default();
+        mmu.write(0xC123, 0x42);
+        assert_eq!(mmu.read(0xE123), 0x42);
+
+        mmu.write(0xFDFF, 0x24);
+        assert_eq!(mmu.read(0xDDFF), 0x24);
+    }
+
+    #[test]
+    fn joypad_reads_selected_group() {
+        let mut mmu = TestMmu::default();
+        mmu.update_input_registers(Button::A | Button::Down);
+
+        mmu.write(JOYP, 0x10); // select action buttons
+        assert_eq!(mmu.read(JOYP), 0xDE);
+
+        mmu.write(JOYP, 0x20); // select direction keys
+        assert_eq!(mmu.read(JOYP), 0xE7);
+
+        mmu.write(JOYP, 0x30);
+        assert_eq!(mmu.read(JOYP), 0xFF);
+    }
+
+    #[test]
+    fn joypad_press_requests_interrupt() {
+        let mut mmu = TestMmu::default();
+        mmu.write(INTERRUPT_FLAG, 0x00);
+        mmu.write(JOYP, 0x10);
+
+        // direction keys aren't selected, so pressing one doesn't pull a line low
+        mmu.update_input_registers(Button::Left.into());
+        assert_eq!(mmu.read(INTERRUPT_FLAG), 0xE0);
+
+        mmu.update_input_registers(Button::Left | Button::Start);
+        assert_eq!(mmu.read(INTERRUPT_FLAG), 0xF0);
+    }
+
+    #[test]
+    fn speed_switch_requires_preparation() {
+        let mut mmu = TestMmu::default();
+        assert!(!mmu.try_switch_speed());
+
+        mmu.write(KEY1, 0x01);
+        assert_eq!(mmu.read(KEY1), 0x7F);
+        assert!(mmu.try_switch_speed());
+        assert!(mmu.is_double_speed());
+        assert_eq!(mmu.read(KEY1), 0xFE);
+    }
+}
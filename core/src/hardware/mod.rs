@@ -20,6 +20,9 @@ pub use self::cartridge::Cartridge;
 pub mod cpu;
 pub use self::cpu::Cpu;
 
+pub mod interrupt;
+pub use self::interrupt::Interrupt;
+
 pub mod ppu;
 pub use self::ppu::Ppu;
 
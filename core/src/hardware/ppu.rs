@@ -7,25 +7,471 @@
 // except according to those terms.
 
 //! Picture processing unit type
+//!
+//! Each line takes 456 dots: an OAM scan, then drawing, then horizontal blank. The 144 visible
+//! lines are followed by 10 lines of vertical blank. A whole line is rendered at once when
+//! drawing starts.
 
 use super::mmu::Swram;
 use super::Mmu;
 use hardware::bios::Bios;
+use hardware::interrupt::Interrupt;
+use hardware::memory::addresses::memory_map::{OAM_OFFSET, VRAM_OFFSET};
+use hardware::memory::addresses::registers::{BGP, LCDC, LCDS, LYC, OBP0, OBP1};
+use hardware::memory::addresses::registers::{SCX, SCY, WX, WY};
+use hardware::memory::Memory;
+use isa::{Address, Word};
 
-/// A Gameboy picture processing unit
+/// Width of the screen in pixels
+pub const SCREEN_WIDTH: usize = 160;
+
+/// Height of the screen in pixels
+pub const SCREEN_HEIGHT: usize = 144;
+
+const DOTS_PER_LINE: usize = 456;
+const OAM_SCAN_DOTS: usize = 80;
+const DRAWING_DOTS: usize = 172;
+const LINES_PER_FRAME: Word = 154;
+
+const BG_ENABLE: Word = 0x01;
+const SPRITE_ENABLE: Word = 0x02;
+const TALL_SPRITES: Word = 0x04;
+const BG_TILE_MAP: Word = 0x08;
+const UNSIGNED_TILE_DATA: Word = 0x10;
+const WINDOW_ENABLE: Word = 0x20;
+const WINDOW_TILE_MAP: Word = 0x40;
+const LCD_ENABLE: Word = 0x80;
+
+const HBLANK_INTERRUPT: Word = 0x08;
+const VBLANK_INTERRUPT: Word = 0x10;
+const OAM_SCAN_INTERRUPT: Word = 0x20;
+const COINCIDENCE_INTERRUPT: Word = 0x40;
+
+const SPRITE_BEHIND_BG: Word = 0x80;
+const SPRITE_FLIP_Y: Word = 0x40;
+const SPRITE_FLIP_X: Word = 0x20;
+const SPRITE_PALETTE: Word = 0x10;
+
+const SPRITE_COUNT: usize = 40;
+const SPRITES_PER_LINE: usize = 10;
+
+/// What the PPU is doing, numbered as in the low bits of `STAT`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    HBlank,
+    VBlank,
+    OamScan,
+    Drawing,
+}
+
+impl Mode {
+    fn bits(self) -> Word {
+        match self {
+            Mode::HBlank => 0,
+            Mode::VBlank => 1,
+            Mode::OamScan => 2,
+            Mode::Drawing => 3,
+        }
+    }
+}
+
+/// A sprite from OAM
 #[derive(Debug, Clone, Copy)]
-pub struct Ppu;
+struct Sprite {
+    y: i16,
+    x: i16,
+    tile: Word,
+    attributes: Word,
+}
+
+/// A Gameboy picture processing unit
+#[derive(Debug, Clone)]
+pub struct Ppu {
+    mode: Mode,
+    /// Dots into the current line
+    dots: usize,
+    ly: Word,
+    /// Line of the window to draw next. It only advances on lines the window is drawn on
+    window_line: Word,
+    /// Whether any `STAT` interrupt source was active, since the interrupt fires on a rising
+    /// edge
+    stat_line: bool,
+    enabled: bool,
+    /// Frame being drawn
+    back: Vec<Word>,
+    /// Last completed frame
+    front: Vec<Word>,
+    frame_ready: bool,
+}
 
 impl Ppu {
     /// Emulate the function of a `PPU` over a given number of cycles
-    pub fn emulate<S: Swram>(&mut self, cycles: usize, mmu: &mut Mmu<S>) {
-        debug!("PPU not yet implemented")
-        // TODO: implement PPU::emulate
+    pub fn emulate<S: Swram, B: Bios>(&mut self, cycles: usize, mmu: &mut Mmu<S, B>) {
+        if mmu.read(LCDC) & LCD_ENABLE == 0 {
+            if self.enabled {
+                self.disable(mmu);
+            }
+            return;
+        }
+
+        if !self.enabled {
+            self.enabled = true;
+            self.mode = Mode::OamScan;
+            self.dots = 0;
+            self.ly = 0;
+            self.window_line = 0;
+        }
+        self.update_registers(mmu);
+
+        let mut cycles = cycles;
+        while cycles > 0 {
+            let end = self.mode_end();
+            let step = cycles.min(end - self.dots);
+            self.dots += step;
+            cycles -= step;
+            if self.dots == end {
+                self.next_mode(mmu);
+                self.update_registers(mmu);
+            }
+        }
+    }
+
+    /// Return the current mode
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Return the last completed frame, row by row, as shades from 0 for white to 3 for black
+    pub fn framebuffer(&self) -> &[Word] {
+        &self.front
+    }
+
+    /// Return the last completed frame if it hasn't been taken yet
+    pub fn take_frame(&mut self) -> Option<&[Word]> {
+        if self.frame_ready {
+            self.frame_ready = false;
+            Some(&self.front)
+        } else {
+            None
+        }
+    }
+
+    /// Return the dot the current mode ends on
+    fn mode_end(&self) -> usize {
+        match self.mode {
+            Mode::OamScan => OAM_SCAN_DOTS,
+            Mode::Drawing => OAM_SCAN_DOTS + DRAWING_DOTS,
+            Mode::HBlank | Mode::VBlank => DOTS_PER_LINE,
+        }
+    }
+
+    fn next_mode<S: Swram, B: Bios>(&mut self, mmu: &mut Mmu<S, B>) {
+        self.mode = match self.mode {
+            Mode::OamScan => {
+                self.render_line(mmu);
+                Mode::Drawing
+            }
+            Mode::Drawing => Mode::HBlank,
+            Mode::HBlank => {
+                self.dots = 0;
+                self.ly += 1;
+                if usize::from(self.ly) < SCREEN_HEIGHT {
+                    Mode::OamScan
+                } else {
+                    mmu.request_interrupt(Interrupt::VBlank);
+                    self.present();
+                    Mode::VBlank
+                }
+            }
+            Mode::VBlank => {
+                self.dots = 0;
+                self.ly += 1;
+                if self.ly < LINES_PER_FRAME {
+                    Mode::VBlank
+                } else {
+                    self.ly = 0;
+                    self.window_line = 0;
+                    Mode::OamScan
+                }
+            }
+        };
+    }
+
+    /// Turn the screen off, which blanks it and holds the PPU at the start of the frame
+    fn disable<S: Swram, B: Bios>(&mut self, mmu: &mut Mmu<S, B>) {
+        self.enabled = false;
+        self.mode = Mode::HBlank;
+        self.dots = 0;
+        self.ly = 0;
+        self.stat_line = false;
+        self.back.iter_mut().for_each(|pixel| *pixel = 0);
+        self.present();
+        mmu.update_lcd_registers(0, Mode::HBlank.bits());
+    }
+
+    /// Complete the frame being drawn
+    fn present(&mut self) {
+        self.front.copy_from_slice(&self.back);
+        self.frame_ready = true;
+    }
+
+    /// Update `LY` and `STAT`, requesting a `STAT` interrupt if a selected source became
+    /// active
+    fn update_registers<S: Swram, B: Bios>(&mut self, mmu: &mut Mmu<S, B>) {
+        let stat = mmu.read(LCDS);
+        let coincidence = self.ly == mmu.read(LYC);
+        let source = match self.mode {
+            Mode::HBlank => HBLANK_INTERRUPT,
+            Mode::VBlank => VBLANK_INTERRUPT,
+            Mode::OamScan => OAM_SCAN_INTERRUPT,
+            Mode::Drawing => 0,
+        };
+
+        let line = stat & source != 0 || (coincidence && stat & COINCIDENCE_INTERRUPT != 0);
+        if line && !self.stat_line {
+            mmu.request_interrupt(Interrupt::LcdStat);
+        }
+        self.stat_line = line;
+        mmu.update_lcd_registers(self.ly, (coincidence as Word) << 2 | self.mode.bits());
+    }
+
+    /// Render the current line into the back buffer
+    fn render_line<S: Swram, B: Bios>(&mut self, mmu: &Mmu<S, B>) {
+        let lcdc = mmu.read(LCDC);
+        let ly = self.ly;
+        // colour indices before the palette, which decide if sprites behind the bg show
+        let mut colors = [0; SCREEN_WIDTH];
+
+        if lcdc & BG_ENABLE != 0 {
+            let (scx, scy) = (mmu.read(SCX), mmu.read(SCY));
+            let map = if lcdc & BG_TILE_MAP != 0 { 0x9C00 } else { 0x9800 };
+            for (x, color) in colors.iter_mut().enumerate() {
+                let x = (x as Word).wrapping_add(scx);
+                *color = tile_map_pixel(mmu, lcdc, map, x, ly.wrapping_add(scy));
+            }
+
+            // the window shares the bg enable bit, and is drawn over it from WX - 7
+            let (wx, wy) = (usize::from(mmu.read(WX)), mmu.read(WY));
+            if lcdc & WINDOW_ENABLE != 0 && ly >= wy && wx < SCREEN_WIDTH + 7 {
+                let map = if lcdc & WINDOW_TILE_MAP != 0 { 0x9C00 } else { 0x9800 };
+                for (x, color) in colors.iter_mut().enumerate().skip(wx.saturating_sub(7)) {
+                    let window_x = (x + 7 - wx) as Word;
+                    *color = tile_map_pixel(mmu, lcdc, map, window_x, self.window_line);
+                }
+                self.window_line += 1;
+            }
+        }
+
+        let bgp = mmu.read(BGP);
+        let row = usize::from(ly) * SCREEN_WIDTH;
+        for (pixel, &color) in self.back[row..row + SCREEN_WIDTH].iter_mut().zip(&colors) {
+            *pixel = shade(bgp, color);
+        }
+
+        if lcdc & SPRITE_ENABLE != 0 {
+            self.render_sprites(mmu, lcdc, &colors);
+        }
+    }
+
+    /// Render the sprites on the current line over the bg and window
+    fn render_sprites<S: Swram, B: Bios>(&mut self, mmu: &Mmu<S, B>, lcdc: Word, bg: &[Word]) {
+        let height = if lcdc & TALL_SPRITES != 0 { 16 } else { 8 };
+        let ly = i16::from(self.ly);
+
+        // only the first ten sprites in OAM on a line are drawn. Where they overlap, the one
+        // furthest left wins, then the one first in OAM
+        let mut sprites: Vec<Sprite> = (0..SPRITE_COUNT)
+            .map(|i| {
+                let address = OAM_OFFSET + i as Address * 4;
+                Sprite {
+                    y: i16::from(mmu.read(address)) - 16,
+                    x: i16::from(mmu.read(address + 1)) - 8,
+                    tile: mmu.read(address + 2),
+                    attributes: mmu.read(address + 3),
+                }
+            })
+            .filter(|sprite| ly >= sprite.y && ly < sprite.y + height)
+            .take(SPRITES_PER_LINE)
+            .collect();
+        sprites.sort_by_key(|sprite| sprite.x);
+
+        let (obp0, obp1) = (mmu.read(OBP0), mmu.read(OBP1));
+        let row = usize::from(self.ly) * SCREEN_WIDTH;
+        for (x, &bg_color) in bg.iter().enumerate() {
+            let x = x as i16;
+            let pixel = sprites
+                .iter()
+                .filter(|sprite| x >= sprite.x && x < sprite.x + 8)
+                .map(|sprite| (sprite, sprite_pixel(mmu, sprite, height, x, ly)))
+                .find(|&(_, color)| color != 0);
+
+            if let Some((sprite, color)) = pixel {
+                if sprite.attributes & SPRITE_BEHIND_BG != 0 && bg_color != 0 {
+                    continue;
+                }
+                let palette = if sprite.attributes & SPRITE_PALETTE != 0 {
+                    obp1
+                } else {
+                    obp0
+                };
+                self.back[row + x as usize] = shade(palette, color);
+            }
+        }
     }
 }
 
+/// Return the colour index of a pixel in a bg or window tile map
+fn tile_map_pixel<S: Swram, B: Bios>(
+    mmu: &Mmu<S, B>,
+    lcdc: Word,
+    map: Address,
+    x: Word,
+    y: Word,
+) -> Word {
+    let tile = mmu.read(map + Address::from(y / 8) * 32 + Address::from(x / 8));
+    let address = if lcdc & UNSIGNED_TILE_DATA != 0 {
+        VRAM_OFFSET + Address::from(tile) * 16
+    } else {
+        // tiles 0x80 to 0xFF come before 0x9000, shared with the unsigned tile data
+        (0x9000 + i32::from(tile as i8) * 16) as Address
+    };
+    tile_pixel(mmu, address + Address::from(y % 8) * 2, x % 8)
+}
+
+/// Return the colour index of a sprite's pixel
+fn sprite_pixel<S: Swram, B: Bios>(
+    mmu: &Mmu<S, B>,
+    sprite: &Sprite,
+    height: i16,
+    x: i16,
+    y: i16,
+) -> Word {
+    let mut row = y - sprite.y;
+    let mut column = x - sprite.x;
+    if sprite.attributes & SPRITE_FLIP_Y != 0 {
+        row = height - 1 - row;
+    }
+    if sprite.attributes & SPRITE_FLIP_X != 0 {
+        column = 7 - column;
+    }
+
+    // tall sprites ignore the lowest bit of their tile, and use the next tile for the bottom
+    let tile = if height == 16 {
+        sprite.tile & 0xFE
+    } else {
+        sprite.tile
+    };
+    let address = VRAM_OFFSET + Address::from(tile) * 16 + row as Address * 2;
+    tile_pixel(mmu, address, column as Word)
+}
+
+/// Return the colour index of a pixel in a row of tile data. Each row is two bytes, holding
+/// the low and high bit of each pixel from left to right
+fn tile_pixel<S: Swram, B: Bios>(mmu: &Mmu<S, B>, row: Address, x: Word) -> Word {
+    let bit = 7 - x;
+    let low = mmu.read(row) >> bit & 0x01;
+    let high = mmu.read(row + 1) >> bit & 0x01;
+    high << 1 | low
+}
+
+/// Return the shade a palette maps a colour index to
+fn shade(palette: Word, color: Word) -> Word {
+    palette >> (color * 2) & 0x03
+}
+
 impl Default for Ppu {
     fn default() -> Self {
-        Ppu {}
+        Ppu {
+            mode: Mode::OamScan,
+            dots: 0,
+            ly: 0,
+            window_line: 0,
+            stat_line: false,
+            enabled: false,
+            back: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            front: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            frame_ready: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use hardware::bios::GbBios;
+    use hardware::memory::addresses::registers::{INTERRUPT_FLAG, LY};
+    use hardware::mmu::swram::Fixed;
+
+    type TestMmu = Mmu<Fixed, GbBios>;
+
+    const FRAME_DOTS: usize = DOTS_PER_LINE * LINES_PER_FRAME as usize;
+
+    #[test]
+    fn steps_through_modes_and_lines() {
+        let mut mmu = TestMmu::default();
+        let mut ppu = Ppu::default();
+        mmu.write(INTERRUPT_FLAG, 0x00);
+        mmu.write(LYC, 2);
+        mmu.write(LCDS, COINCIDENCE_INTERRUPT);
+
+        ppu.emulate(OAM_SCAN_DOTS - 1, &mut mmu);
+        assert_eq!(mmu.read(LCDS) & 0x07, 2);
+        ppu.emulate(1, &mut mmu);
+        assert_eq!(mmu.read(LCDS) & 0x07, 3);
+        ppu.emulate(DRAWING_DOTS, &mut mmu);
+        assert_eq!(mmu.read(LCDS) & 0x07, 0);
+
+        ppu.emulate(DOTS_PER_LINE * 2 - OAM_SCAN_DOTS - DRAWING_DOTS, &mut mmu);
+        assert_eq!(mmu.read(LY), 2);
+        assert_eq!(mmu.read(LCDS) & 0x07, 0x06);
+        assert_eq!(mmu.read(INTERRUPT_FLAG), 0xE2);
+
+        ppu.emulate(DOTS_PER_LINE * (SCREEN_HEIGHT - 2), &mut mmu);
+        assert_eq!((mmu.read(LY), ppu.mode()), (144, Mode::VBlank));
+        assert_eq!(mmu.read(INTERRUPT_FLAG), 0xE3);
+        assert!(ppu.take_frame().is_some());
+        assert!(ppu.take_frame().is_none());
+
+        ppu.emulate(FRAME_DOTS - DOTS_PER_LINE * SCREEN_HEIGHT, &mut mmu);
+        assert_eq!((mmu.read(LY), ppu.mode()), (0, Mode::OamScan));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn renders_bg_window_and_sprites() {
+        let mut mmu = TestMmu::default();
+        let mut ppu = Ppu::default();
+
+        // tile 1 is solid colour 3, tile 2 has colour 1 in its left column only
+        for row in 0..8 {
+            mmu.write(0x8010 + row * 2, 0xFF);
+            mmu.write(0x8011 + row * 2, 0xFF);
+            mmu.write(0x8020 + row * 2, 0x80);
+        }
+        mmu.write(0x9800, 0x01);
+        mmu.write(SCX, 4);
+        mmu.write(BGP, 0xE4);
+
+        // the window starts at x 80 with tile 2 from the second map
+        mmu.write(0x9C00, 0x02);
+        mmu.write(WX, 87);
+        mmu.write(WY, 0);
+        mmu.write(LCDC, 0x91 | WINDOW_ENABLE | WINDOW_TILE_MAP | SPRITE_ENABLE);
+
+        // a sprite of tile 1 at x 40, and one behind the bg at x 0
+        for &(i, x, attributes) in &[(0, 48, SPRITE_PALETTE), (1, 8, SPRITE_BEHIND_BG)] {
+            mmu.write(OAM_OFFSET + i * 4, 16);
+            mmu.write(OAM_OFFSET + i * 4 + 1, x);
+            mmu.write(OAM_OFFSET + i * 4 + 2, 0x01);
+            mmu.write(OAM_OFFSET + i * 4 + 3, attributes);
+        }
+        mmu.write(OBP0, 0x80);
+        mmu.write(OBP1, 0x40);
+
+        ppu.emulate(FRAME_DOTS, &mut mmu);
+        let frame = ppu.take_frame().unwrap();
+        assert_eq!(&frame[0..9], &[3, 3, 3, 3, 2, 2, 2, 2, 0]);
+        assert_eq!(&frame[40..49], &[1, 1, 1, 1, 1, 1, 1, 1, 0]);
+        assert_eq!(&frame[80..82], &[1, 0]);
+        assert_eq!(frame[SCREEN_WIDTH * 8], 0);
+    }
+}
@@ -109,6 +109,29 @@ impl fmt::Display for Flag {
     }
 }
 
+/// A branch condition
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Condition {
+    NotZero,
+    Zero,
+    NotCarry,
+    Carry,
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::Condition::*;
+        let st = match *self {
+            NotZero => "NZ",
+            Zero => "Z",
+            NotCarry => "NC",
+            Carry => "C",
+        };
+
+        write!(f, "{}", st)
+    }
+}
+
 /// A Gameboy CPU Instruction
 ///
 /// # Notes
@@ -132,10 +155,10 @@ pub enum Instruction {
     LdARr(Register16),
     LdAI(Immediate8),
     LdRrA(Register16),
-    LdIA(Immediate8),
+    LdAIi(Immediate16),
     LdAIo(Immediate8),
     LdIoA(Immediate8),
-    LdIiA,
+    LdIiA(Immediate16),
     LdAIoc,
     LdIocA,
 
@@ -261,17 +284,17 @@ pub enum Instruction {
 
     // Jump commands
     JpIi(Immediate16),
-    JpCondIi(Flag, Immediate16),
+    JpCondIi(Condition, Immediate16),
     JpHl,
 
     JrS(SignedImmediate8),
-    JrCondS(Flag, SignedImmediate8),
+    JrCondS(Condition, SignedImmediate8),
 
     CallIi(Address),
-    CallCondIi(Flag, Address),
+    CallCondIi(Condition, Address),
 
     Ret,
-    RetCond(Flag),
+    RetCond(Condition),
 
     Reti,
 
@@ -288,7 +311,6 @@ impl Instruction {
     /// Assumes the instruction is not branching
     pub fn cycles(self) -> u8 {
         use self::Instruction::*;
-        // TODO:  implement Instruction::cycles()
         match self {
             // 8-bit load instructions
             LdRR(_, _) => 4,
@@ -297,12 +319,12 @@ impl Instruction {
             LdHlR(_) => 8,
             LdHlI(_) => 12,
             LdARr(_) => 8,
-            LdAI(_) => 16,
+            LdAI(_) => 8,
             LdRrA(_) => 8,
-            LdIA(_) => 16,
+            LdAIi(_) => 16,
             LdAIo(_) => 12,
             LdIoA(_) => 12,
-            LdIiA => 16,
+            LdIiA(_) => 16,
             LdAIoc => 8,
             LdIocA => 8,
 
@@ -413,10 +435,10 @@ impl Instruction {
             BitIHl(_) => 12,
 
             SetIR(_, _) => 8,
-            SetIHl(_) => 12,
+            SetIHl(_) => 16,
 
             ResIR(_, _) => 8,
-            ResIHl(_) => 12,
+            ResIHl(_) => 16,
 
             // Control commands
             Ccf => 4,
@@ -427,7 +449,7 @@ impl Instruction {
 
             Halt => 4,
 
-            Stop => 0,
+            Stop => 4,
 
             Di => 4,
 
@@ -438,7 +460,7 @@ impl Instruction {
             JpCondIi(_, _) => 12,
 
             JrS(_) => 12,
-            JrCondS(_, _) => 12,
+            JrCondS(_, _) => 8,
 
             CallIi(_) => 24,
             CallCondIi(_, _) => 12,
@@ -450,7 +472,7 @@ impl Instruction {
 
             Rst(_) => 16,
 
-            Undefined(_) => 0,
+            Undefined(_) => 4,
         }
     }
 
@@ -469,6 +491,7 @@ impl Instruction {
         }
     }
 
+    /// Return the size of an instruction in bytes, including its operands
     pub fn size(self) -> u8 {
         use self::Instruction::*;
         match self {
@@ -481,12 +504,12 @@ impl Instruction {
             LdARr(_) => 1,
             LdAI(_) => 2,
             LdRrA(_) => 1,
-            LdIA(_) => 1,
-            LdAIo(_) => 1,
-            LdIoA(_) => 1,
-            LdIiA => 3,
-            LdAIoc => 2,
-            LdIocA => 2,
+            LdAIi(_) => 3,
+            LdAIo(_) => 2,
+            LdIoA(_) => 2,
+            LdIiA(_) => 3,
+            LdAIoc => 1,
+            LdIocA => 1,
 
             LdiHlA => 1,
             LdiAHl => 1,
@@ -632,7 +655,7 @@ impl Instruction {
 
             Rst(_) => 1,
 
-            Undefined(_) => 0,
+            Undefined(_) => 1,
         }
     }
 }
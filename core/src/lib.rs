@@ -23,6 +23,9 @@ extern crate failure;
 #[macro_use]
 extern crate enumset;
 
+extern crate flate2;
+extern crate zip;
+
 pub mod disasm;
 
 pub mod hardware;
@@ -30,3 +33,5 @@ pub mod hardware;
 pub mod system;
 
 pub mod isa;
+
+pub mod loader;
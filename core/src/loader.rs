@@ -0,0 +1,166 @@
+// Copyright 2018 Will Johnston
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Loading cartridges from files
+//!
+//! Roms can be raw `.gb`/`.gbc` images, or compressed in a `.zip` or `.gz` file. Files are told
+//! apart by their contents rather than their extension.
+
+use std::fs;
+use std::io::{self, Cursor, Read};
+use std::path::Path;
+
+use failure::Error;
+use flate2::read::GzDecoder;
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+use hardware::Cartridge;
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const GZIP_MAGIC: &[u8] = &[0x1F, 0x8B];
+
+/// Extensions of rom images inside zip files
+const ROM_EXTENSIONS: &[&str] = &[".gb", ".gbc"];
+
+/// The largest rom image decompressed, so archives can't claim or inflate to any size. This is
+/// the size of the largest MBC5 rom
+const MAX_ROM_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Load a cartridge from a rom file. Zip files load their first rom image
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Cartridge, Error> {
+    Cartridge::try_parse_bytes(&read_rom(path, None)?)
+}
+
+/// Load a cartridge from a named entry of a zip file
+pub fn load_entry<P: AsRef<Path>>(path: P, entry: &str) -> Result<Cartridge, Error> {
+    Cartridge::try_parse_bytes(&read_rom(path, Some(entry))?)
+}
+
+/// Read the rom image from a file without parsing it, so it can be patched first. `entry`
+/// names the image to use from a zip file, and is ignored for other files
+pub fn read_rom<P: AsRef<Path>>(path: P, entry: Option<&str>) -> Result<Vec<u8>, Error> {
+    let path = path.as_ref();
+    let bytes = fs::read(path).map_err(|error| LoadError::Read {
+        path: path.display().to_string(),
+        error,
+    })?;
+    extract(bytes, entry)
+}
+
+/// Decompress a rom image, if it is compressed
+fn extract(bytes: Vec<u8>, entry: Option<&str>) -> Result<Vec<u8>, Error> {
+    if bytes.starts_with(ZIP_MAGIC) {
+        extract_zip(bytes, entry)
+    } else if bytes.starts_with(GZIP_MAGIC) {
+        decompress(GzDecoder::new(&bytes[..]))
+    } else {
+        Ok(bytes)
+    }
+}
+
+fn extract_zip(bytes: Vec<u8>, entry: Option<&str>) -> Result<Vec<u8>, Error> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+    let name = match entry {
+        Some(name) => name.to_owned(),
+        None => first_rom(&mut archive)?.ok_or(LoadError::NoRomInArchive)?,
+    };
+
+    let mut file = archive.by_name(&name).map_err(|error| match error {
+        ZipError::FileNotFound => LoadError::MissingEntry { name: name.clone() }.into(),
+        error => Error::from(error),
+    })?;
+    decompress(&mut file)
+}
+
+/// Read a compressed rom image, failing if it inflates past `MAX_ROM_SIZE`
+fn decompress<R: Read>(reader: R) -> Result<Vec<u8>, Error> {
+    let mut rom = Vec::new();
+    reader.take(MAX_ROM_SIZE + 1).read_to_end(&mut rom)?;
+    if rom.len() as u64 > MAX_ROM_SIZE {
+        return Err(LoadError::TooLarge.into());
+    }
+    Ok(rom)
+}
+
+/// Return the name of the first rom image in a zip file
+fn first_rom(archive: &mut ZipArchive<Cursor<Vec<u8>>>) -> Result<Option<String>, Error> {
+    for i in 0..archive.len() {
+        let name = archive.by_index(i)?.name().to_owned();
+        let lowercase = name.to_lowercase();
+        if ROM_EXTENSIONS.iter().any(|extension| lowercase.ends_with(extension)) {
+            return Ok(Some(name));
+        }
+    }
+    Ok(None)
+}
+
+/// Errors that can occur loading a rom file
+#[derive(Fail, Debug)]
+pub enum LoadError {
+    #[fail(display = "Failed to read {}: {}", path, error)]
+    Read {
+        path: String,
+        #[cause]
+        error: io::Error,
+    },
+    #[fail(display = "Zip file contains no .gb or .gbc rom")]
+    NoRomInArchive,
+    #[fail(display = "Zip file has no entry named {}", name)]
+    MissingEntry { name: String },
+    #[fail(display = "Compressed rom is larger than 8MB")]
+    TooLarge,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    fn zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for &(name, contents) in entries {
+            writer.start_file(name, FileOptions::default()).unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn extracts_roms_from_zip_files() {
+        let bytes = zip(&[("README.txt", b"readme"), ("Game.GB", b"first"), ("b.gbc", b"second")]);
+        assert_eq!(extract(bytes.clone(), None).unwrap(), b"first");
+        assert_eq!(extract(bytes.clone(), Some("b.gbc")).unwrap(), b"second");
+        assert!(extract(bytes, Some("c.gb")).is_err());
+        assert!(extract(zip(&[("README.txt", b"readme")]), None).is_err());
+    }
+
+    #[test]
+    fn decompresses_gzip_files() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[0x12; 0x8000]).unwrap();
+        let bytes = encoder.finish().unwrap();
+        assert_eq!(extract(bytes, None).unwrap(), vec![0x12; 0x8000]);
+        assert_eq!(extract(vec![0x00, 0x01], None).unwrap(), vec![0x00, 0x01]);
+    }
+
+    #[test]
+    fn rejects_roms_that_inflate_past_the_limit() {
+        let huge = vec![0; MAX_ROM_SIZE as usize + 1];
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&huge).unwrap();
+        assert!(extract(encoder.finish().unwrap(), None).is_err());
+        assert!(extract(zip(&[("huge.gb", &huge)]), None).is_err());
+
+        let limit = &huge[1..];
+        assert_eq!(extract(zip(&[("limit.gb", limit)]), None).unwrap().len(), limit.len());
+    }
+}
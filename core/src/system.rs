@@ -8,15 +8,26 @@
 
 //! Full emulator systems
 
+use std::fs;
+use std::path::PathBuf;
+
 use enumset::EnumSet;
+use failure::Error;
 use hardware::bios::{Bios, CgbBios, GbBios};
 
+use hardware::cartridge::camera::ImagePort;
+use hardware::cartridge::header::{ENTRY_POINT_OFFSET, HEADER_CHECKSUM_ADDRESS};
+use hardware::cartridge::infrared::InfraredPort;
+use hardware::cartridge::speaker::SpeakerPort;
 use hardware::cartridge::Cartridge;
-use hardware::cpu::Registers;
-use hardware::memory::Memory8Kb;
+use hardware::cpu::{Registers, IDLE_CYCLES};
+use hardware::memory::{Memory, Memory8Kb};
 use hardware::mmu::swram::{self, Swram};
 use hardware::{Apu, Cpu, Mmu, Ppu};
-use isa::Address;
+use isa::{Address, DoubleWord, Word};
+
+/// Stack pointer value every boot rom leaves behind
+const POST_BOOT_SP: DoubleWord = 0xFFFE;
 
 /// Gameboy
 pub type Gb = System<swram::Fixed, GbBios>;
@@ -27,6 +38,13 @@ pub type Cgb = System<swram::Banked, CgbBios>;
 /// Gameboy Keys state
 pub type Buttons = EnumSet<Button>;
 
+/// Tilt of the cartridge along each axis, in units of gravity. Zero is held flat
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Tilt {
+    pub x: f32,
+    pub y: f32,
+}
+
 /// A gameboy button
 enum_set_type! {
     pub enum Button {
@@ -41,22 +59,121 @@ enum_set_type! {
     }
 }
 
+/// A Gameboy hardware model. Each model's boot rom leaves the system in a slightly different
+/// state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Model {
+    /// Early gameboy with the first revision of the boot rom
+    Dmg0,
+    /// Gameboy
+    Dmg,
+    /// Gameboy pocket
+    Mgb,
+    /// Super gameboy
+    Sgb,
+    /// Gameboy color
+    Cgb,
+    /// Gameboy advance, running gameboy color software
+    Agb,
+}
+
+impl Model {
+    /// Returns true if the model runs in gameboy color mode
+    pub fn is_cgb(&self) -> bool {
+        match *self {
+            Model::Cgb | Model::Agb => true,
+            _ => false,
+        }
+    }
+
+    /// Return the register file as the boot rom leaves it. On the gameboy and pocket the half
+    /// carry and carry flags depend on the header checksum of the inserted cartridge
+    pub fn post_boot_registers(&self, header_checksum: Word) -> Registers {
+        let checksum_flags = if header_checksum == 0 { 0x80 } else { 0xB0 };
+        let (a, f, b, c, d, e, h, l) = match *self {
+            Model::Dmg0 => (0x01, 0x00, 0xFF, 0x13, 0x00, 0xC1, 0x84, 0x03),
+            Model::Dmg => (0x01, checksum_flags, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D),
+            Model::Mgb => (0xFF, checksum_flags, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D),
+            Model::Sgb => (0x01, 0x00, 0x00, 0x14, 0x00, 0x00, 0xC0, 0x60),
+            Model::Cgb => (0x11, 0x80, 0x00, 0x00, 0xFF, 0x56, 0x00, 0x0D),
+            Model::Agb => (0x11, 0x00, 0x01, 0x00, 0xFF, 0x56, 0x00, 0x0D),
+        };
+
+        Registers {
+            a,
+            f,
+            b,
+            c,
+            d,
+            e,
+            h,
+            l,
+            sp: POST_BOOT_SP,
+            pc: ENTRY_POINT_OFFSET,
+        }
+    }
+}
+
+/// A policy for writing battery backed cartridge ram to a file, when the cartridge is unloaded
+/// and periodically while the ram is dirty
+#[derive(Debug, Clone)]
+pub struct Autosave {
+    path: PathBuf,
+    /// Emulated cycles between saves
+    interval: usize,
+    /// Cycles counted towards the next save
+    cycles: usize,
+}
+
+impl Autosave {
+    /// Create a policy saving to `path` at most once every `interval` emulated cycles
+    pub fn new<P: Into<PathBuf>>(path: P, interval: usize) -> Self {
+        Autosave {
+            path: path.into(),
+            interval,
+            cycles: 0,
+        }
+    }
+}
+
 /// A Gameboy sytem
 pub struct System<S: Swram, B: Bios> {
     input: Buttons,
-    cpu: Cpu<B>,
-    mmu: Mmu<S>,
+    tilt: Tilt,
+    autosave: Option<Autosave>,
+    cpu: Cpu,
+    mmu: Mmu<S, B>,
     gpu: Ppu,
     apu: Apu,
 }
 
 impl<S: Swram + Default, B: Bios> System<S, B> {
-    /// Create a new system with no loaded catridge
+    /// Create a new system with no loaded catridge that starts by running the bios
     pub fn new(bios: B) -> Self {
         System {
             input: Buttons::empty(),
-            cpu: Cpu::new(bios),
-            mmu: Mmu::default(),
+            tilt: Tilt::default(),
+            autosave: None,
+            cpu: Cpu::new(),
+            mmu: Mmu::with_bios(bios),
+            gpu: Ppu::default(),
+            apu: Apu::default(),
+        }
+    }
+
+    /// Create a new system that skips the bios and starts running `cartridge` from its entry
+    /// point, in the state the boot rom of `model` would have left it
+    pub fn without_bios(model: Model, cartridge: Cartridge) -> Self {
+        let mut mmu = Mmu::post_boot(model);
+        mmu.load(cartridge);
+        let registers = model.post_boot_registers(mmu.read(HEADER_CHECKSUM_ADDRESS));
+
+        System {
+            input: Buttons::empty(),
+            tilt: Tilt::default(),
+            autosave: None,
+            cpu: Cpu::with_registers(registers),
+            mmu,
             gpu: Ppu::default(),
             apu: Apu::default(),
         }
@@ -64,25 +181,94 @@ impl<S: Swram + Default, B: Bios> System<S, B> {
 }
 
 impl<S: Swram, B: Bios> System<S, B> {
-    /// Load a catridge into the system and return the old one if there was one
+    /// Load a catridge into the system and return the old one if there was one. The old
+    /// cartridge is saved first if autosave is enabled
     pub fn load(&mut self, cartridge: Cartridge) -> Option<Cartridge> {
+        self.autosave_or_log();
         self.mmu.load(cartridge)
     }
 
-    /// Unload the current cartridge, if there is one, and return it
+    /// Unload the current cartridge, if there is one, and return it. It is saved first if
+    /// autosave is enabled
     pub fn maybe_unload(&mut self) -> Option<Cartridge> {
+        self.autosave_or_log();
         self.mmu.maybe_unload()
     }
 
+    /// Save battery backed cartridge ram according to `autosave`, or stop autosaving if it's
+    /// `None`
+    pub fn set_autosave(&mut self, autosave: Option<Autosave>) {
+        self.autosave = autosave;
+    }
+
+    /// Write battery backed cartridge ram to the autosave file if it has changed since it was
+    /// last saved. Does nothing if autosave is disabled
+    pub fn autosave(&mut self) -> Result<(), Error> {
+        let autosave = match self.autosave {
+            Some(ref mut autosave) => autosave,
+            None => return Ok(()),
+        };
+        autosave.cycles = 0;
+
+        if let Some(cartridge) = self.mmu.cartridge_mut() {
+            if cartridge.is_dirty() {
+                if let Some(save) = cartridge.export_save() {
+                    fs::write(&autosave.path, save)?;
+                }
+                cartridge.mark_saved();
+            }
+        }
+        Ok(())
+    }
+
+    /// Autosave, logging errors where there is no caller to return them to
+    fn autosave_or_log(&mut self) {
+        if let Err(e) = self.autosave() {
+            error!("Failed to autosave: {}", e);
+        }
+    }
+
     /// Step the sytem forward on instruction execution
     pub fn step(&mut self) -> u8 {
-        // TODO: need to check interrupts
         self.mmu.update_input_registers(self.input); // update input state
+        self.mmu.update_tilt(self.tilt);
 
-        let cycles_in_step = self.cpu.step(&mut self.mmu);
+        if self.cpu.is_stopped() {
+            // nothing is clocked in stop mode, and only joypad input leaves it
+            if self.input.is_empty() {
+                return IDLE_CYCLES;
+            }
+            self.cpu.resume();
+        }
 
-        self.gpu.emulate(cycles_in_step as usize, &mut self.mmu);
-        self.apu.emulate(cycles_in_step as usize, &mut self.mmu);
+        let interrupt_cycles = self.cpu.service_interrupts(&mut self.mmu);
+        let cycles_in_step = interrupt_cycles + self.cpu.step(&mut self.mmu);
+
+        // on the CGB, stop also performs a speed switch prepared through KEY1
+        if self.cpu.is_stopped() && self.mmu.try_switch_speed() {
+            self.cpu.resume();
+        }
+
+        // peripherals run at the same pace regardless of CPU speed
+        let peripheral_cycles = if self.mmu.is_double_speed() {
+            cycles_in_step as usize / 2
+        } else {
+            cycles_in_step as usize
+        };
+
+        self.mmu.emulate_cartridge(peripheral_cycles);
+        let autosave_due = match self.autosave {
+            Some(ref mut autosave) => {
+                autosave.cycles += peripheral_cycles;
+                autosave.cycles >= autosave.interval
+            }
+            None => false,
+        };
+        if autosave_due {
+            self.autosave_or_log();
+        }
+        self.gpu.emulate(peripheral_cycles, &mut self.mmu);
+        self.apu.emulate(peripheral_cycles, &mut self.mmu);
         cycles_in_step
     }
 
@@ -107,6 +293,51 @@ impl<S: Swram, B: Bios> System<S, B> {
         self.input
     }
 
+    /// Set the tilt the cartridge accelerometer will read from
+    pub fn set_tilt(&mut self, tilt: Tilt) {
+        self.tilt = tilt;
+    }
+
+    /// Return current tilt state
+    pub fn tilt(&self) -> Tilt {
+        self.tilt
+    }
+
+    /// Connect an infrared transceiver to the loaded cartridge. Cartridges without an
+    /// infrared port ignore it
+    pub fn connect_infrared(&mut self, port: InfraredPort) {
+        self.mmu.connect_infrared(port)
+    }
+
+    /// Connect a tone generator to the loaded cartridge. Cartridges without a speaker ignore
+    /// it
+    pub fn connect_speaker(&mut self, port: SpeakerPort) {
+        self.mmu.connect_speaker(port)
+    }
+
+    /// Connect an image provider to the loaded cartridge. Cartridges without a camera ignore
+    /// it
+    pub fn connect_camera(&mut self, port: ImagePort) {
+        self.mmu.connect_camera(port)
+    }
+
+    /// Returns true if the rumble motor of the loaded cartridge is running
+    pub fn is_rumbling(&self) -> bool {
+        self.mmu.is_rumbling()
+    }
+
+    /// Return the last frame the PPU completed, row by row, as shades from 0 for white to 3
+    /// for black
+    pub fn framebuffer(&self) -> &[Word] {
+        self.gpu.framebuffer()
+    }
+
+    /// Return the frame the PPU completed most recently, once per frame. Returns `None` if no
+    /// frame has completed since the last call
+    pub fn take_frame(&mut self) -> Option<&[Word]> {
+        self.gpu.take_frame()
+    }
+
     /// Return the sytem video ram
     pub fn vram(&self) -> &Memory8Kb {
         self.mmu.vram()
@@ -123,7 +354,70 @@ impl<S: Swram, B: Bios> System<S, B> {
     }
 
     /// Return a reference to the memory managment unit (MMU)
-    pub fn mmu(&self) -> &Mmu<S> {
+    pub fn mmu(&self) -> &Mmu<S, B> {
         &self.mmu
     }
 }
+
+impl<S: Swram, B: Bios> Drop for System<S, B> {
+    fn drop(&mut self) {
+        self.autosave_or_log();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    use hardware::cpu::CYCLES_PER_SECOND;
+    use isa::Register16;
+
+    /// A temporary file, removed when dropped so failing tests clean up too
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        /// Name a file in the temp directory unique to this process and test
+        fn new(test: &str) -> Self {
+            TempFile(env::temp_dir().join(format!("sgbe-{}-{}.sav", process::id(), test)))
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn without_bios_starts_at_entry_point() {
+        let cartridge = Cartridge::try_parse_bytes(&[0; 0x8000]).unwrap();
+        let system = Gb::without_bios(Model::Dmg, cartridge);
+        let registers = system.registers();
+
+        assert!(!system.mmu().is_bios_mapped());
+        assert_eq!(system.pc(), 0x0100);
+        assert_eq!(registers.sp, 0xFFFE);
+        assert_eq!((registers.a, registers.f), (0x01, 0x80));
+        assert_eq!(registers.read_register16(Register16::HL), 0x014D);
+    }
+
+    #[test]
+    fn autosaves_dirty_ram_on_unload() {
+        let file = TempFile::new("autosaves_dirty_ram_on_unload");
+        let mut bytes = vec![0; 0x8000];
+        bytes[0x147] = 0x03; // MBC1, ram and battery
+        bytes[0x149] = 0x02; // 8KB
+        let cartridge = Cartridge::try_parse_bytes(&bytes).unwrap();
+
+        let mut system = Gb::without_bios(Model::Dmg, cartridge);
+        system.set_autosave(Some(Autosave::new(file.0.clone(), CYCLES_PER_SECOND)));
+        system.mmu.write(0x0000, 0x0A);
+        system.mmu.write(0xA000, 0x42);
+        system.maybe_unload();
+
+        let save = fs::read(&file.0).unwrap();
+        assert_eq!((save.len(), save[0]), (0x2000, 0x42));
+    }
+}
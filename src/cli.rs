@@ -8,26 +8,52 @@
 
 #[macro_use]
 extern crate failure;
+#[macro_use]
+extern crate log;
 extern crate core;
 
 use core::system::{Cgb, Gb};
+use core::hardware::cartridge::patch;
 use core::hardware::Cartridge;
+use core::loader;
 use failure::Error;
 use std::env;
 use std::fs;
-use std::io::Read;
 
 fn main() -> Result<(), Error> {
-    let mut args = env::args();
-    let _ = args.next();
+    let mut args = env::args().skip(1);
+    let mut positional = Vec::new();
+    let mut patches = Vec::new();
+    let mut entry = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--patch" => {
+                let path = args.next().ok_or_else(|| format_err!("--patch needs a file"))?;
+                patches.push(path);
+            }
+            "--entry" => {
+                entry = Some(args.next().ok_or_else(|| format_err!("--entry needs a name"))?);
+            }
+            _ => positional.push(arg),
+        }
+    }
+    let mut args = positional.into_iter();
 
-    let cartridge = {
-        let mut fh = fs::File::open(args.next().unwrap())?;
-        let mut buffer = String::new();
-        let _ = fh.read_to_string(&mut buffer)?;
-        let bytes = buffer.as_bytes();
-        Cartridge::try_parse_bytes(bytes)
+    let path = args.next().ok_or_else(|| format_err!("missing rom path"))?;
+    let entry = entry.as_ref().map(String::as_str);
+    let cartridge = if patches.is_empty() {
+        match entry {
+            Some(entry) => loader::load_entry(path, entry)?,
+            None => loader::load(path)?,
+        }
+    } else {
+        let mut bytes = loader::read_rom(path, entry)?;
+        for path in &patches {
+            bytes = patch::apply(&bytes, &fs::read(path)?)?;
+        }
+        Cartridge::try_parse_bytes(&bytes)?
     };
+    info!("Loaded {}", cartridge.header().title);
 
     let bios = fs::File::open(args.next().unwrap())?;
     let mode = Some("gb");